@@ -0,0 +1,234 @@
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use std::fmt;
+use std::io::Cursor;
+
+// More than this many sounds waiting on a channel and new ones are dropped, so a burst of cues
+// can't pile up seconds of backlog
+const MAX_QUEUED: usize = 4;
+
+pub type Sound = Box<dyn Source<Item = f32> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Cues,  // Phase cues and announcements
+    Beeps, // Countdown and alert tones, kept apart so they never wait behind a cue
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AudioError {
+    Unavailable(String), // No output device or sink, shown next to "Test sound"
+    Busy(usize),         // The channel was full, none of this many sounds were queued
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AudioError::Unavailable(err) => write!(f, "{}", err),
+            AudioError::Busy(count) => write!(f, "Audio queue full, dropped {} sound(s)", count),
+        }
+    }
+}
+
+// Where queued sounds end up and how encoded ones are read, the sound card outside of tests
+pub trait Output {
+    fn decode(&self, data: Vec<u8>) -> Result<Sound, String>;
+    // Sounds on the channel that are playing or still waiting
+    fn queued(&mut self, channel: Channel) -> Result<usize, String>;
+    fn append(&mut self, channel: Channel, sound: Sound) -> Result<(), String>;
+    // Cuts both channels off right away
+    fn clear(&mut self);
+}
+
+// The channels in front of an output: sounds are appended, so cues requested close together
+// play one after another instead of cutting each other off
+pub struct AudioOut {
+    output: Box<dyn Output>,
+}
+
+impl Default for AudioOut {
+    fn default() -> Self {
+        Self::new(Box::new(Speakers::default()))
+    }
+}
+
+impl AudioOut {
+    pub fn new(output: Box<dyn Output>) -> Self {
+        Self { output }
+    }
+
+    // The sounds go together or not at all, a cue never plays half its announcement.
+    // A channel too busy to take them all is an AudioError::Busy.
+    pub fn queue(&mut self, channel: Channel, sounds: Vec<Sound>) -> Result<(), AudioError> {
+        let queued = self.output.queued(channel).map_err(AudioError::Unavailable)?;
+        if queued + sounds.len() > MAX_QUEUED {
+            return Err(AudioError::Busy(sounds.len()));
+        }
+        for sound in sounds {
+            self.output.append(channel, sound).map_err(AudioError::Unavailable)?;
+        }
+        Ok(())
+    }
+
+    // For Stop, Dismiss and a new start
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    pub fn decode(&self, data: Vec<u8>) -> Result<Sound, String> {
+        self.output.decode(data)
+    }
+}
+
+// MP3, WAV, OGG Vorbis or FLAC, whichever rodio was built with
+fn decode(data: Vec<u8>) -> Result<Sound, String> {
+    let source = Decoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+    Ok(Box::new(source.convert_samples()))
+}
+
+// One output stream for the whole run and a long-lived sink per channel
+#[derive(Default)]
+struct Speakers {
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    cues: Option<Sink>,
+    beeps: Option<Sink>,
+}
+
+impl Output for Speakers {
+    fn decode(&self, data: Vec<u8>) -> Result<Sound, String> {
+        decode(data)
+    }
+
+    fn queued(&mut self, channel: Channel) -> Result<usize, String> {
+        Ok(self.sink(channel)?.len())
+    }
+
+    fn append(&mut self, channel: Channel, sound: Sound) -> Result<(), String> {
+        self.sink(channel)?.append(sound);
+        Ok(())
+    }
+
+    // Dropping a sink cuts it off right away, unlike Sink::clear, which waits for the audio
+    // thread; the next sound opens a new one
+    fn clear(&mut self) {
+        self.cues = None;
+        self.beeps = None;
+    }
+}
+
+impl Speakers {
+    fn sink(&mut self, channel: Channel) -> Result<&Sink, String> {
+        let (_, handle) = match &mut self.output {
+            Some(output) => output,
+            empty => empty
+                .insert(OutputStream::try_default().map_err(|err| format!("No audio output available: {}", err))?),
+        };
+        let slot = match channel {
+            Channel::Cues => &mut self.cues,
+            Channel::Beeps => &mut self.beeps,
+        };
+        match slot {
+            Some(sink) => Ok(sink),
+            empty => Ok(empty.insert(Sink::try_new(handle).map_err(|err| format!("Failed to create audio sink: {}", err))?)),
+        }
+    }
+}
+
+// Keeps every queued sound instead of playing it, nothing ever finishes
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MockOutput {
+    pub sounds: std::rc::Rc<std::cell::RefCell<Vec<(Channel, Sound)>>>,
+    pub unavailable: bool,
+    pub undecodable: bool, // Like a build whose decoders can't read any of the cues
+}
+
+#[cfg(test)]
+impl Output for MockOutput {
+    fn decode(&self, data: Vec<u8>) -> Result<Sound, String> {
+        if self.undecodable {
+            return Err("Unrecognized format".to_string());
+        }
+        decode(data)
+    }
+
+    fn queued(&mut self, channel: Channel) -> Result<usize, String> {
+        if self.unavailable {
+            return Err("No audio output available: mock".to_string());
+        }
+        Ok(self.sounds.borrow().iter().filter(|(queued, _)| *queued == channel).count())
+    }
+
+    fn append(&mut self, channel: Channel, sound: Sound) -> Result<(), String> {
+        self.sounds.borrow_mut().push((channel, sound));
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.sounds.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::source::SineWave;
+    use std::time::Duration;
+
+    // A tone whose length in samples tells the sounds apart
+    fn tone(millis: u64) -> Sound {
+        Box::new(SineWave::new(440.0).take_duration(Duration::from_millis(millis)))
+    }
+
+    fn lengths(mock: &MockOutput, channel: Channel) -> Vec<usize> {
+        let mut sounds = mock.sounds.borrow_mut();
+        sounds.iter_mut().filter(|(queued, _)| *queued == channel).map(|(_, sound)| sound.by_ref().count()).collect()
+    }
+
+    fn samples(millis: u64) -> usize {
+        tone(millis).count()
+    }
+
+    #[test]
+    fn sounds_play_in_the_order_they_were_queued() {
+        let mock = MockOutput::default();
+        let mut audio = AudioOut::new(Box::new(mock.clone()));
+        audio.queue(Channel::Cues, vec![tone(10), tone(20)]).unwrap();
+        audio.queue(Channel::Beeps, vec![tone(5)]).unwrap();
+        audio.queue(Channel::Cues, vec![tone(30)]).unwrap();
+        assert_eq!(lengths(&mock, Channel::Cues), vec![samples(10), samples(20), samples(30)]);
+        assert_eq!(lengths(&mock, Channel::Beeps), vec![samples(5)]);
+    }
+
+    #[test]
+    fn a_full_channel_reports_the_whole_batch_as_dropped() {
+        let mock = MockOutput::default();
+        let mut audio = AudioOut::new(Box::new(mock.clone()));
+        audio.queue(Channel::Cues, vec![tone(10), tone(10), tone(10)]).unwrap();
+        assert_eq!(audio.queue(Channel::Cues, vec![tone(20), tone(20)]), Err(AudioError::Busy(2)));
+        assert_eq!(lengths(&mock, Channel::Cues), vec![samples(10); 3]);
+
+        // The other channel is not held up, and what fits still goes in
+        audio.queue(Channel::Beeps, vec![tone(5), tone(5), tone(5), tone(5)]).unwrap();
+        audio.queue(Channel::Cues, vec![tone(20)]).unwrap();
+        assert_eq!(lengths(&mock, Channel::Cues).len(), 4);
+    }
+
+    #[test]
+    fn a_missing_output_is_unavailable() {
+        let mock = MockOutput { unavailable: true, ..Default::default() };
+        let mut audio = AudioOut::new(Box::new(mock.clone()));
+        let result = audio.queue(Channel::Cues, vec![tone(10)]);
+        assert!(matches!(result, Err(AudioError::Unavailable(_))));
+        assert!(mock.sounds.borrow().is_empty());
+    }
+
+    #[test]
+    fn clear_empties_the_channels() {
+        let mock = MockOutput::default();
+        let mut audio = AudioOut::new(Box::new(mock.clone()));
+        audio.queue(Channel::Cues, vec![tone(10), tone(10), tone(10), tone(10)]).unwrap();
+        audio.clear();
+        audio.queue(Channel::Cues, vec![tone(20)]).unwrap();
+        assert_eq!(lengths(&mock, Channel::Cues), vec![samples(20)]);
+    }
+}
@@ -0,0 +1,49 @@
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+
+use crate::paths;
+
+// Holds the path of the newest report until the next launch has shown it
+const PENDING_FILE: &str = "crash-pending.txt";
+
+// The windows subsystem has no console, so without this a panic just closes the window
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map_or_else(|| "unknown location".to_string(), |l| format!("{}:{}", l.file(), l.line()));
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let report = format!(
+            "{} v{} crashed at {}\n\n{}\nat {}\n\n{}\n",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            message,
+            location,
+            Backtrace::force_capture()
+        );
+        let path = paths::data_file(&format!("crash-{}.txt", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        // Nothing here may panic again, failed writes are simply dropped
+        if fs::write(&path, report).is_ok() {
+            let _ = fs::write(paths::data_file(PENDING_FILE), path.to_string_lossy().as_bytes());
+        }
+        default_hook(info);
+    }));
+}
+
+// Report left by the previous run, returned only once
+pub fn take_pending_report() -> Option<PathBuf> {
+    let marker = paths::data_file(PENDING_FILE);
+    let path = fs::read_to_string(&marker).ok()?;
+    let _ = fs::remove_file(&marker);
+    let path = PathBuf::from(path.trim());
+    path.exists().then_some(path)
+}
@@ -0,0 +1,111 @@
+#[cfg(feature = "discord")]
+use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+#[cfg(feature = "discord")]
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The Discord application has to be registered by whoever ships the build
+#[cfg(feature = "discord")]
+const APPLICATION_ID: Option<&str> = option_env!("DISCORD_APPLICATION_ID");
+#[cfg(not(feature = "discord"))]
+const APPLICATION_ID: Option<&str> = None;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(15);
+#[cfg(feature = "discord")]
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[cfg_attr(not(feature = "discord"), allow(dead_code))] // Only the IPC worker reads it
+pub struct PresenceStatus {
+    pub details: String,
+    pub state: String,
+}
+
+// Owns the IPC worker thread; all Discord communication happens there so a hung client can't block a frame
+#[derive(Default)]
+pub struct DiscordPresence {
+    tx: Option<Sender<Option<PresenceStatus>>>,
+    last_details: Option<Option<String>>,
+    last_sent: Option<Instant>,
+}
+
+impl DiscordPresence {
+    pub fn available() -> bool {
+        APPLICATION_ID.is_some()
+    }
+
+    pub fn update(&mut self, enabled: bool, status: Option<PresenceStatus>) {
+        let Some(application_id) = APPLICATION_ID.filter(|_| enabled) else {
+            // Dropping the sender makes the worker clear the activity and exit
+            self.tx = None;
+            self.last_details = None;
+            return;
+        };
+        let tx = self.tx.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || worker(application_id, rx));
+            tx
+        });
+
+        // Push on phase or round changes, and refresh the countdown every few seconds
+        let details = status.as_ref().map(|status| status.details.clone());
+        let changed = self.last_details.as_ref() != Some(&details);
+        let refresh = status.is_some() && self.last_sent.is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL);
+        if changed || refresh {
+            let _ = tx.send(status);
+            self.last_details = Some(details);
+            self.last_sent = Some(Instant::now());
+        }
+    }
+}
+
+// Never started without the feature, APPLICATION_ID is None then
+#[cfg(not(feature = "discord"))]
+fn worker(_application_id: &str, _rx: Receiver<Option<PresenceStatus>>) {}
+
+#[cfg(feature = "discord")]
+fn worker(application_id: &str, rx: Receiver<Option<PresenceStatus>>) {
+    let mut client = DiscordIpcClient::new(application_id);
+    let mut connected = false;
+    let mut last_attempt: Option<Instant> = None;
+    let mut current: Option<PresenceStatus> = None;
+    let mut pending = false;
+
+    loop {
+        match rx.recv_timeout(RECONNECT_INTERVAL) {
+            Ok(status) => {
+                current = status;
+                pending = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // Discord may not be running, quietly retry now and then
+        if !connected && last_attempt.is_none_or(|t| t.elapsed() >= RECONNECT_INTERVAL) {
+            last_attempt = Some(Instant::now());
+            connected = client.connect().is_ok();
+            log::debug!("Discord connection attempt, connected: {}", connected);
+        }
+
+        if connected && pending {
+            let result = match &current {
+                Some(status) => client.set_activity(
+                    Activity::new().details(status.details.as_str()).state(status.state.as_str()),
+                ),
+                None => client.clear_activity(),
+            };
+            if result.is_ok() {
+                pending = false;
+            } else {
+                connected = false;
+                let _ = client.close();
+            }
+        }
+    }
+
+    if connected {
+        let _ = client.clear_activity();
+        let _ = client.close();
+    }
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::PathBuf;
+
+// Fonts that come with the OS and cover Chinese, Japanese and Korean, the first one found is used
+#[cfg(windows)]
+const SYSTEM_FALLBACKS: &[&str] = &["msyh.ttc", "YuGothM.ttc", "msgothic.ttc", "malgun.ttf", "simsun.ttc"];
+#[cfg(target_os = "macos")]
+const SYSTEM_FALLBACKS: &[&str] = &[
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/AppleSDGothicNeo.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+#[cfg(not(any(windows, target_os = "macos")))]
+const SYSTEM_FALLBACKS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/droid/DroidSansFallbackFull.ttf",
+];
+
+fn system_path(name: &str) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let windows = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+        PathBuf::from(windows).join("Fonts").join(name)
+    }
+    #[cfg(not(windows))]
+    PathBuf::from(name)
+}
+
+// egui panics on font data it can't parse, so everything is checked here first
+fn read_font(path: &PathBuf) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    ab_glyph::FontRef::try_from_slice(&data).map_err(|_| format!("{} is not a TTF, OTF or TTC font", path.display()))?;
+    Ok(data)
+}
+
+// The font for characters the built-in ones lack: the configured file, or else the first system
+// font from the list. Ok(None) when there is none, the UI then shows boxes for those characters.
+pub fn load_fallback(custom: &str) -> Result<Option<Vec<u8>>, String> {
+    let custom = custom.trim();
+    if !custom.is_empty() {
+        return read_font(&PathBuf::from(custom)).map(Some);
+    }
+    let found = SYSTEM_FALLBACKS.iter().map(|name| system_path(name)).find(|path| path.is_file());
+    match found {
+        Some(path) => {
+            log::info!("Using {} for characters outside the built-in fonts", path.display());
+            read_font(&path).map(Some)
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,159 @@
+// Shared time formatting: MM:SS, or H:MM:SS once a value reaches an hour
+pub fn clock(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+// "1 round", "2 rounds"
+pub fn count(n: u64, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", n, noun)
+    }
+}
+
+// Position in the session, counting up ("Round 7/10") or down ("3 rounds left", "Final round").
+// A total of 0 has no set end, like the stopwatch, so only the round is shown.
+pub fn round_progress(round: u32, total: u32, count_down: bool) -> String {
+    let left = total.saturating_sub(round);
+    if total == 0 {
+        format!("Round {}", round)
+    } else if !count_down {
+        format!("Round {}/{}", round, total)
+    } else if left == 0 {
+        "Final round".to_string()
+    } else {
+        format!("{} left", count(left as u64, "round"))
+    }
+}
+
+// Time of day for the wall clock, "14:05" or "2:05 PM", with ":09" seconds when asked for
+pub fn time_of_day(time: impl chrono::Timelike, hour24: bool, seconds: bool) -> String {
+    let secs = if seconds { format!(":{:02}", time.second()) } else { String::new() };
+    if hour24 {
+        format!("{:02}:{:02}{}", time.hour(), time.minute(), secs)
+    } else {
+        let (pm, hour) = time.hour12();
+        format!("{}:{:02}{} {}", hour, time.minute(), secs, if pm { "PM" } else { "AM" })
+    }
+}
+
+// Typed durations: plain seconds ("90"), or M:SS / H:MM:SS like clock() shows them
+pub fn parse_duration(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let text = text.strip_suffix('s').unwrap_or(text).trim_end();
+    if text.is_empty() || text.split(':').count() > 3 {
+        return None;
+    }
+    text.split(':').try_fold(0u64, |total, part| {
+        let part: u64 = part.trim().parse().ok()?;
+        total.checked_mul(60)?.checked_add(part)
+    })
+}
+
+// "45 s", "90 min", "1:30" for the bounds in validation messages
+pub fn span(secs: u64) -> String {
+    if secs < 60 {
+        format!("{} s", secs)
+    } else if secs.is_multiple_of(60) {
+        format!("{} min", secs / 60)
+    } else {
+        clock(secs)
+    }
+}
+
+// A typed value checked against its range, with a message for the field when it doesn't fit.
+// Durations take the formats of parse_duration, other values whole numbers.
+pub fn validate_input(text: &str, min: u64, max: u64, duration: bool) -> Result<u64, String> {
+    let (value, bounds) = if duration {
+        (parse_duration(text), format!("{} and {}", span(min), span(max)))
+    } else {
+        (text.trim().parse().ok(), format!("{} and {}", min, max))
+    };
+    match value {
+        Some(value) if (min..=max).contains(&value) => Ok(value),
+        Some(_) => Err(format!("Must be between {}", bounds)),
+        None if duration => Err("Enter seconds or M:SS".to_string()),
+        None => Err("Enter a whole number".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_switches_to_hours_at_one_hour() {
+        assert_eq!(clock(0), "00:00");
+        assert_eq!(clock(59), "00:59");
+        assert_eq!(clock(3599), "59:59");
+        assert_eq!(clock(3600), "1:00:00");
+        assert_eq!(clock(36000), "10:00:00");
+    }
+
+    #[test]
+    fn count_pluralizes_everything_but_one() {
+        assert_eq!(count(0, "round"), "0 rounds");
+        assert_eq!(count(1, "round"), "1 round");
+        assert_eq!(count(2, "round"), "2 rounds");
+    }
+
+    #[test]
+    fn round_progress_counts_up_and_down() {
+        assert_eq!(round_progress(1, 1, false), "Round 1/1");
+        assert_eq!(round_progress(1, 1, true), "Final round");
+        assert_eq!(round_progress(3, 10, false), "Round 3/10");
+        assert_eq!(round_progress(3, 10, true), "7 rounds left");
+        assert_eq!(round_progress(9, 10, true), "1 round left");
+        assert_eq!(round_progress(10, 10, false), "Round 10/10");
+        assert_eq!(round_progress(10, 10, true), "Final round");
+    }
+
+    #[test]
+    fn round_progress_without_an_end_shows_the_round() {
+        assert_eq!(round_progress(1, 0, false), "Round 1");
+        assert_eq!(round_progress(4, 0, true), "Round 4");
+    }
+
+    #[test]
+    fn parse_duration_takes_seconds_and_clock_times() {
+        assert_eq!(parse_duration("0"), Some(0));
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration(" 45s "), Some(45));
+        assert_eq!(parse_duration("1:30"), Some(90));
+        assert_eq!(parse_duration("1:00:05"), Some(3605));
+        assert_eq!(parse_duration("9999"), Some(9999));
+    }
+
+    #[test]
+    fn parse_duration_rejects_everything_else() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("   "), None);
+        assert_eq!(parse_duration("abc"), None);
+        assert_eq!(parse_duration("1:"), None);
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration("1:2:3:4"), None);
+    }
+
+    #[test]
+    fn validate_input_checks_the_range() {
+        assert_eq!(validate_input("0", 0, 60, true), Ok(0));
+        assert_eq!(validate_input("1:00", 0, 60, true), Ok(60));
+        assert_eq!(validate_input("9999", 5, 5400, true), Err("Must be between 5 s and 90 min".to_string()));
+        assert_eq!(validate_input("0", 1, 99, false), Err("Must be between 1 and 99".to_string()));
+        assert_eq!(validate_input(" 12 ", 1, 99, false), Ok(12));
+    }
+
+    #[test]
+    fn validate_input_explains_what_to_type() {
+        assert_eq!(validate_input("abc", 0, 60, true), Err("Enter seconds or M:SS".to_string()));
+        assert_eq!(validate_input("", 0, 60, true), Err("Enter seconds or M:SS".to_string()));
+        assert_eq!(validate_input("abc", 1, 99, false), Err("Enter a whole number".to_string()));
+        assert_eq!(validate_input("", 1, 99, false), Err("Enter a whole number".to_string()));
+        assert_eq!(validate_input("1:30", 1, 99, false), Err("Enter a whole number".to_string()));
+    }
+}
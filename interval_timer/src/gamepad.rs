@@ -0,0 +1,91 @@
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+#[cfg(feature = "gamepad")]
+const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))] // Only the input thread sends them
+pub enum PadCommand {
+    StartPause, // A
+    Stop, // B
+    Skip, // RB
+    Adjust(i64), // D-pad up and down, in seconds
+}
+
+// Reads every connected controller on a background thread. Gilrs reports controllers being
+// plugged in and out by itself, so one connected after startup works too.
+pub struct GamepadInput {
+    rx: Receiver<PadCommand>,
+    stop: Arc<AtomicBool>,
+}
+
+impl GamepadInput {
+    pub fn available() -> bool {
+        cfg!(feature = "gamepad")
+    }
+
+    #[cfg(feature = "gamepad")]
+    pub fn start(ctx: egui::Context) -> Self {
+        use gilrs::{Button, EventType};
+
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        std::thread::spawn(move || {
+            // Created on the thread, Gilrs isn't Send on every platform
+            let mut gilrs = match gilrs::Gilrs::new() {
+                Ok(gilrs) => gilrs,
+                Err(err) => {
+                    log::warn!("Gamepad support unavailable: {}", err);
+                    return;
+                }
+            };
+            while !thread_stop.load(Ordering::Relaxed) {
+                let Some(event) = gilrs.next_event_blocking(Some(POLL_TIMEOUT)) else {
+                    continue; // Timeout, check whether to stop
+                };
+                let command = match event.event {
+                    EventType::ButtonPressed(Button::South, _) => PadCommand::StartPause,
+                    EventType::ButtonPressed(Button::East, _) => PadCommand::Stop,
+                    EventType::ButtonPressed(Button::RightTrigger, _) => PadCommand::Skip,
+                    EventType::ButtonPressed(Button::DPadUp, _) => PadCommand::Adjust(5),
+                    EventType::ButtonPressed(Button::DPadDown, _) => PadCommand::Adjust(-5),
+                    EventType::Connected => {
+                        log::info!("Gamepad connected: {}", gilrs.gamepad(event.id).name());
+                        continue;
+                    }
+                    EventType::Disconnected => {
+                        log::info!("Gamepad disconnected");
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if tx.send(command).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+        Self { rx, stop }
+    }
+
+    // Nothing ever arrives without controller support compiled in
+    #[cfg(not(feature = "gamepad"))]
+    pub fn start(_ctx: egui::Context) -> Self {
+        let (_, rx) = mpsc::channel();
+        Self { rx, stop: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub fn poll(&self) -> Vec<PadCommand> {
+        self.rx.try_iter().collect()
+    }
+}
+
+impl Drop for GamepadInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,436 @@
+use chrono::{DateTime, Datelike, Local, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::format;
+
+pub const HISTORY_FILE: &str = "history.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub finished_at: i64, // Unix seconds
+    pub rounds: u32,
+    pub workout_duration: u64,
+    pub rest_duration: u64,
+    pub work_secs: u64,
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub total_secs: u64, // Wall clock time from Start to completion
+    #[serde(default)]
+    pub pauses: u32,
+    #[serde(default)]
+    pub kcal: Option<f32>, // Only when a body weight was set
+    #[serde(default)]
+    pub paused_secs: u64,
+    #[serde(default)]
+    pub planned_secs: u64, // Session length without pauses or changes, 0 in older records
+    #[serde(default)]
+    pub pomodoro: bool, // Focus sessions are counted apart from workouts
+    #[serde(default)]
+    pub overtime_secs: u64, // Past the planned end of phases, with manual advance
+    #[serde(default)]
+    pub lap_secs: Vec<f64>, // Split times, stopwatch mode only
+    #[serde(default)]
+    pub rest_secs: u64,
+    #[serde(default)]
+    pub planned_work_secs: u64, // 0 in older records and stopwatch sessions, no comparison then
+    #[serde(default)]
+    pub planned_rest_secs: u64,
+}
+
+impl SessionRecord {
+    pub fn local_date(&self) -> NaiveDate {
+        DateTime::from_timestamp(self.finished_at, 0)
+            .unwrap_or_default()
+            .with_timezone(&Local)
+            .date_naive()
+    }
+
+    // Same tag, or for untagged sessions the same durations and rounds
+    fn comparable_to(&self, other: &SessionRecord) -> bool {
+        if self.pomodoro != other.pomodoro {
+            false
+        } else if self.tag.is_empty() {
+            other.tag.is_empty()
+                && self.workout_duration == other.workout_duration
+                && self.rest_duration == other.rest_duration
+                && self.rounds == other.rounds
+        } else {
+            self.tag == other.tag
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Totals {
+    pub sessions: usize,
+    pub work_secs: u64,
+    pub streak_days: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct History {
+    pub records: Vec<SessionRecord>,
+}
+
+impl History {
+    // Also returns a notice for the user when the file was there but unreadable
+    pub fn load_from_file(path: &Path) -> (Self, Option<String>) {
+        let Ok(data) = fs::read_to_string(path) else {
+            return (Self::default(), None);
+        };
+        match serde_json::from_str(&data) {
+            Ok(history) => (history, None),
+            Err(err) => {
+                log::warn!("History file is unreadable ({}), starting empty", err);
+                (Self::default(), Some(Self::set_aside_corrupt(path)))
+            }
+        }
+    }
+
+    // Moves an unreadable history out of the way so the next session never overwrites it
+    fn set_aside_corrupt(path: &Path) -> String {
+        let backup = path.with_file_name(format!(
+            "{}.corrupt-{}",
+            path.file_name().unwrap_or(HISTORY_FILE.as_ref()).to_string_lossy(),
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        match fs::rename(path, &backup) {
+            Ok(()) => format!(
+                "Your history file was unreadable, so the history starts out empty. A backup was kept at {}",
+                backup.display()
+            ),
+            Err(err) => {
+                log::error!("Could not back up {}: {}", path.display(), err);
+                format!(
+                    "Your history file was unreadable, so the history starts out empty. It couldn't be backed up ({}), \
+                     copy {} somewhere safe before finishing a session",
+                    err,
+                    path.display()
+                )
+            }
+        }
+    }
+
+    // Written next to the file and renamed over it, so a crash mid-save never truncates the history
+    pub fn save_to_file(&self, path: &Path) {
+        let Ok(data) = serde_json::to_string_pretty(self) else {
+            return;
+        };
+        let name = path.file_name().unwrap_or(HISTORY_FILE.as_ref()).to_string_lossy();
+        let temp = path.with_file_name(format!("{}.tmp", name));
+        if let Err(err) = fs::write(&temp, data).and_then(|()| fs::rename(&temp, path)) {
+            log::error!("Failed to save history to {}: {}", path.display(), err);
+            let _ = fs::remove_file(&temp);
+        }
+    }
+
+    pub fn add(&mut self, record: SessionRecord, path: &Path) {
+        self.records.push(record);
+        self.save_to_file(path);
+    }
+
+    // Everything, as the plain history file, so a backup restores without loss
+    pub fn backup(&self, path: &Path) -> Result<(), String> {
+        let data = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        fs::write(path, data).map_err(|err| format!("Could not write {}: {}", path.display(), err))
+    }
+
+    // Merges a backup into the history, sessions are the same when they finished at the same second.
+    // Returns how many were added and how many were already there.
+    pub fn restore(&mut self, path: &Path, history_path: &Path) -> Result<(usize, usize), String> {
+        let data = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        let backup: History = serde_json::from_str(&data).map_err(|err| format!("Not a history backup: {}", err))?;
+        let mut known: BTreeSet<i64> = self.records.iter().map(|record| record.finished_at).collect();
+        let total = backup.records.len();
+        let added: Vec<SessionRecord> =
+            backup.records.into_iter().filter(|record| known.insert(record.finished_at)).collect();
+        let count = added.len();
+        if count > 0 {
+            self.records.extend(added);
+            self.records.sort_by_key(|record| record.finished_at);
+            self.save_to_file(history_path);
+        }
+        Ok((count, total - count))
+    }
+
+    // Previously used tags for the picker, sorted and without duplicates
+    pub fn tags(&self) -> Vec<String> {
+        let tags: BTreeSet<&str> = self
+            .records
+            .iter()
+            .map(|record| record.tag.as_str())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.into_iter().map(str::to_string).collect()
+    }
+
+    pub fn filtered<'a>(&'a self, tag: Option<&'a str>, pomodoro: bool) -> impl Iterator<Item = &'a SessionRecord> {
+        self.records
+            .iter()
+            .filter(move |record| record.pomodoro == pomodoro && tag.is_none_or(|tag| record.tag == tag))
+    }
+
+    pub fn totals(&self, tag: Option<&str>, pomodoro: bool, today: NaiveDate) -> Totals {
+        let mut totals = Totals::default();
+        let mut days = BTreeSet::new();
+        for record in self.filtered(tag, pomodoro) {
+            totals.sessions += 1;
+            totals.work_secs += record.work_secs;
+            days.insert(record.local_date());
+        }
+
+        // Consecutive training days ending today, or yesterday if today isn't done yet
+        let mut day = if days.contains(&today) { Some(today) } else { today.pred_opt() };
+        while let Some(current) = day.filter(|d| days.contains(d)) {
+            totals.streak_days += 1;
+            day = current.pred_opt();
+        }
+        totals
+    }
+
+    // The most recent comparable session before the given (usually just finished) one
+    pub fn previous_comparable(&self, record: &SessionRecord) -> Option<&SessionRecord> {
+        self.records
+            .iter()
+            .rev()
+            .filter(|other| other.finished_at < record.finished_at)
+            .find(|other| record.comparable_to(other))
+    }
+
+    // Average pauses per session for each of the last `weeks` weeks, oldest first, None for weeks
+    // without sessions
+    pub fn weekly_pause_averages(
+        &self,
+        tag: Option<&str>,
+        pomodoro: bool,
+        today: NaiveDate,
+        first_day: Weekday,
+        weeks: u64,
+    ) -> Vec<Option<f32>> {
+        let this_week = week_start(today, first_day);
+        (0..weeks)
+            .rev()
+            .map(|weeks_back| {
+                let start = this_week - chrono::Days::new(7 * weeks_back);
+                let end = start + chrono::Days::new(7);
+                let (sessions, pauses) = self
+                    .filtered(tag, pomodoro)
+                    .filter(|record| (start..end).contains(&record.local_date()))
+                    .fold((0u32, 0u32), |(sessions, pauses), record| (sessions + 1, pauses + record.pauses));
+                (sessions > 0).then(|| pauses as f32 / sessions as f32)
+            })
+            .collect()
+    }
+
+    // Work seconds per day from `first` on, days without sessions are left out
+    pub fn daily_work_secs(&self, tag: Option<&str>, pomodoro: bool, first: NaiveDate) -> BTreeMap<NaiveDate, u64> {
+        let mut days = BTreeMap::new();
+        for record in self.filtered(tag, pomodoro) {
+            let date = record.local_date();
+            if date >= first {
+                *days.entry(date).or_insert(0) += record.work_secs;
+            }
+        }
+        days
+    }
+
+    // Sessions and work seconds since the start of the current week
+    pub fn week_totals(&self, today: NaiveDate, first_day: Weekday, pomodoro: bool) -> (u32, u64) {
+        let week_start = week_start(today, first_day);
+        self.filtered(None, pomodoro)
+            .filter(|record| record.local_date() >= week_start)
+            .fold((0, 0), |(sessions, work_secs), record| (sessions + 1, work_secs + record.work_secs))
+    }
+}
+
+pub fn week_start(today: NaiveDate, first_day: Weekday) -> NaiveDate {
+    let days_into_week = (7 + today.weekday().num_days_from_monday() - first_day.num_days_from_monday()) % 7;
+    today - chrono::Days::new(days_into_week as u64)
+}
+
+// One line summary of how a session went compared to the previous comparable one
+pub fn comparison_text(current: &SessionRecord, previous: Option<&SessionRecord>) -> String {
+    let Some(previous) = previous else {
+        return "No earlier session to compare with".to_string();
+    };
+    format!(
+        "vs last time: rounds {} {}, time {} {}, pauses {} {}",
+        current.rounds,
+        trend(current.rounds as i64 - previous.rounds as i64),
+        format::clock(current.total_secs),
+        trend(current.total_secs as i64 - previous.total_secs as i64),
+        current.pauses,
+        trend(current.pauses as i64 - previous.pauses as i64),
+    )
+}
+
+// "You rested 1:42 more than planned", only for phases that differ from the plan
+pub fn planned_vs_actual(record: &SessionRecord) -> Vec<String> {
+    if record.planned_work_secs == 0 {
+        return Vec::new();
+    }
+    [
+        ("worked", record.work_secs, record.planned_work_secs),
+        ("rested", record.rest_secs, record.planned_rest_secs),
+    ]
+    .into_iter()
+    .filter(|(_, actual, planned)| actual != planned)
+    .map(|(verb, actual, planned)| {
+        let more = if actual > planned { "more" } else { "less" };
+        format!("You {} {} {} than planned", verb, format::clock(actual.abs_diff(planned)), more)
+    })
+    .collect()
+}
+
+fn trend(delta: i64) -> &'static str {
+    match delta.signum() {
+        1 => "↑",
+        -1 => "↓",
+        _ => "=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(finished_at: i64, tag: &str, workout: u64, rounds: u32, total: u64, pauses: u32) -> SessionRecord {
+        serde_json::from_value(serde_json::json!({
+            "finished_at": finished_at,
+            "rounds": rounds,
+            "workout_duration": workout,
+            "rest_duration": 30,
+            "work_secs": workout * rounds as u64,
+            "tag": tag,
+            "total_secs": total,
+            "pauses": pauses,
+        }))
+        .unwrap()
+    }
+
+    fn history() -> History {
+        History {
+            records: vec![
+                record(100, "", 60, 8, 720, 0),
+                record(200, "legs", 45, 10, 750, 1),
+                record(300, "", 45, 8, 600, 2),
+                record(400, "", 60, 8, 700, 1),
+                record(500, "legs", 30, 6, 400, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn previous_comparable_matches_untagged_sessions_by_durations() {
+        let history = history();
+        let current = record(600, "", 60, 8, 690, 1);
+        assert_eq!(history.previous_comparable(&current).map(|r| r.finished_at), Some(400));
+
+        let different = record(600, "", 60, 9, 690, 1);
+        assert!(history.previous_comparable(&different).is_none());
+    }
+
+    #[test]
+    fn previous_comparable_matches_tagged_sessions_by_tag() {
+        let history = history();
+        let current = record(450, "legs", 60, 8, 690, 1);
+        assert_eq!(history.previous_comparable(&current).map(|r| r.finished_at), Some(200));
+    }
+
+    #[test]
+    fn previous_comparable_only_looks_back() {
+        let history = history();
+        assert_eq!(history.previous_comparable(&history.records[3]).map(|r| r.finished_at), Some(100));
+        assert!(history.previous_comparable(&history.records[0]).is_none());
+    }
+
+    #[test]
+    fn previous_comparable_keeps_pomodoro_apart() {
+        let history = history();
+        let mut focus = record(600, "", 60, 8, 690, 1);
+        focus.pomodoro = true;
+        assert!(history.previous_comparable(&focus).is_none());
+    }
+
+    #[test]
+    fn comparison_text_shows_the_trend_of_each_value() {
+        let current = record(600, "", 60, 9, 650, 1);
+        let previous = record(400, "", 60, 8, 700, 1);
+        assert_eq!(
+            comparison_text(&current, Some(&previous)),
+            "vs last time: rounds 9 ↑, time 10:50 ↓, pauses 1 ="
+        );
+        assert_eq!(comparison_text(&current, None), "No earlier session to compare with");
+    }
+
+    fn corrupt_backups(dir: &Path) -> Vec<std::path::PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("history.json.corrupt-"))
+            .collect()
+    }
+
+    #[test]
+    fn truncated_history_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE);
+        let json = serde_json::to_string_pretty(&history()).unwrap();
+        let truncated = &json[..json.len() / 2];
+        fs::write(&path, truncated).unwrap();
+
+        let (mut loaded, notice) = History::load_from_file(&path);
+        assert!(loaded.records.is_empty());
+        let backups = corrupt_backups(dir.path());
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), truncated);
+        assert!(notice.unwrap().contains(&backups[0].display().to_string()));
+
+        // The next session starts a new file and leaves the backup alone
+        loaded.add(record(600, "", 60, 8, 690, 1), &path);
+        assert_eq!(History::load_from_file(&path).0.records.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), truncated);
+    }
+
+    #[test]
+    fn readable_history_loads_without_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE);
+        history().save_to_file(&path);
+        let (loaded, notice) = History::load_from_file(&path);
+        assert_eq!(loaded.records.len(), 5);
+        assert!(notice.is_none());
+        assert!(corrupt_backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn missing_history_starts_empty_without_a_notice() {
+        let dir = tempfile::tempdir().unwrap();
+        let (loaded, notice) = History::load_from_file(&dir.path().join(HISTORY_FILE));
+        assert!(loaded.records.is_empty());
+        assert!(notice.is_none());
+    }
+
+    #[test]
+    fn saving_replaces_the_history_without_leaving_a_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(HISTORY_FILE);
+        fs::write(&path, "{ \"records\": [] }").unwrap();
+        history().save_to_file(&path);
+        assert_eq!(History::load_from_file(&path).0.records.len(), 5);
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, [HISTORY_FILE]);
+    }
+
+    #[test]
+    fn history_that_cant_be_saved_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join(HISTORY_FILE);
+        history().save_to_file(&path);
+        assert!(!path.exists());
+    }
+}
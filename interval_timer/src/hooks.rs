@@ -0,0 +1,96 @@
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Runs the user configured phase commands on background threads and collects their failures
+pub struct HookRunner {
+    error_tx: Sender<String>,
+    error_rx: Receiver<String>,
+    pub last_error: Option<String>,
+}
+
+impl Default for HookRunner {
+    fn default() -> Self {
+        let (error_tx, error_rx) = mpsc::channel();
+        Self {
+            error_tx,
+            error_rx,
+            last_error: None,
+        }
+    }
+}
+
+impl HookRunner {
+    pub fn run(&self, template: &str, phase: &str, round: u32) {
+        if template.trim().is_empty() {
+            return;
+        }
+        let command = template
+            .replace("{round}", &round.to_string())
+            .replace("{phase}", phase);
+        let error_tx = self.error_tx.clone();
+        log::debug!("Running phase command: {}", command);
+
+        // Detached so a slow command can never hold up the UI thread
+        thread::spawn(move || {
+            if let Err(err) = run_with_timeout(&command) {
+                log::warn!("Phase command failed: {}: {}", command, err);
+                let _ = error_tx.send(format!("{}: {}", command, err));
+            }
+        });
+    }
+
+    pub fn poll(&mut self) {
+        while let Ok(err) = self.error_rx.try_recv() {
+            self.last_error = Some(err);
+        }
+    }
+}
+
+fn run_with_timeout(command: &str) -> Result<(), String> {
+    let mut child = shell(command).spawn().map_err(|err| err.to_string())?;
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("exited with {}", status)),
+            Ok(None) if started.elapsed() >= HOOK_TIMEOUT => return Err(kill(child)),
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+}
+
+fn kill(mut child: Child) -> String {
+    let _ = child.kill();
+    let _ = child.wait();
+    format!("killed after {}s", HOOK_TIMEOUT.as_secs())
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command])
+        .creation_flags(CREATE_NO_WINDOW)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    cmd
+}
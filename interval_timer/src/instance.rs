@@ -0,0 +1,98 @@
+use eframe::egui;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// A localhost port works as the instance lock on every platform and is released by the OS
+// even when the process crashes, so a stale lock can never block a launch
+const INSTANCE_PORT: u16 = 47391;
+const MAGIC: &str = "interval_timer";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub enum Startup {
+    Primary(InstanceGuard),
+    AlreadyRunning,
+    Unguarded,
+}
+
+// Held by the running instance, receives messages from later launches
+pub struct InstanceGuard {
+    rx: Receiver<String>,
+    ctx: Arc<Mutex<Option<egui::Context>>>,
+}
+
+impl InstanceGuard {
+    // Lets the listener wake up the UI when a message arrives
+    pub fn set_context(&self, ctx: egui::Context) {
+        if let Ok(mut slot) = self.ctx.lock() {
+            *slot = Some(ctx);
+        }
+    }
+
+    pub fn poll(&self) -> Vec<String> {
+        self.rx.try_iter().collect()
+    }
+}
+
+pub fn claim(message: &str) -> Startup {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, INSTANCE_PORT));
+    match TcpListener::bind(addr) {
+        Ok(listener) => Startup::Primary(listen(listener)),
+        Err(_) if notify_running(addr, message) => Startup::AlreadyRunning,
+        Err(err) => {
+            // Something else owns the port, run without the guard rather than refusing to start
+            log::warn!("Single-instance port unavailable ({}), not guarding", err);
+            Startup::Unguarded
+        }
+    }
+}
+
+fn listen(listener: TcpListener) -> InstanceGuard {
+    let (tx, rx) = mpsc::channel();
+    let ctx: Arc<Mutex<Option<egui::Context>>> = Arc::default();
+    let listener_ctx = ctx.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Some(message) = read_message(&stream) else {
+                continue;
+            };
+            log::info!("Message from another launch: {}", message);
+            let _ = (&stream).write_all(b"ok\n");
+            if tx.send(message).is_err() {
+                break;
+            }
+            if let Ok(slot) = listener_ctx.lock()
+                && let Some(ctx) = slot.as_ref()
+            {
+                ctx.request_repaint();
+            }
+        }
+    });
+
+    InstanceGuard { rx, ctx }
+}
+
+fn read_message(stream: &TcpStream) -> Option<String> {
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT)).ok()?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    let message = line.trim_end().strip_prefix(MAGIC)?.strip_prefix(':')?;
+    Some(message.to_string())
+}
+
+// Only counts as running when our own instance answers, not some unrelated program on the port
+fn notify_running(addr: SocketAddr, message: &str) -> bool {
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(CONNECT_TIMEOUT));
+    if stream.write_all(format!("{}:{}\n", MAGIC, message).as_bytes()).is_err() {
+        return false;
+    }
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply).is_ok() && reply.trim_end() == "ok"
+}
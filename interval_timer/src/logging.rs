@@ -0,0 +1,111 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::paths;
+
+const LOG_FILE: &str = "interval_timer.log";
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+struct Logger {
+    level: Level,
+    console: bool,
+    file: Mutex<Option<LogFile>>,
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Option<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path).ok()?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Some(Self { path, file, size })
+    }
+
+    // Keep a single previous log around once the current one gets too big
+    fn rotate(&mut self) {
+        let _ = fs::rename(&self.path, self.path.with_extension("log.1"));
+        if let Some(fresh) = Self::open(self.path.clone()) {
+            *self = fresh;
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies (winit, eframe, ...) only get to report problems
+        let level = if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            self.level
+        } else {
+            Level::Warn
+        };
+        metadata.level() <= level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.args()
+        );
+        if self.console {
+            eprint!("{}", line);
+        }
+        if let Ok(mut guard) = self.file.lock()
+            && let Some(log_file) = guard.as_mut()
+        {
+            if log_file.size + line.len() as u64 > MAX_LOG_SIZE {
+                log_file.rotate();
+            }
+            if log_file.file.write_all(line.as_bytes()).is_ok() {
+                log_file.size += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut guard) = self.file.lock()
+            && let Some(log_file) = guard.as_mut()
+        {
+            let _ = log_file.file.flush();
+        }
+    }
+}
+
+pub fn init(verbose: bool) {
+    let logger = Logger {
+        level: if verbose { Level::Debug } else { Level::Info },
+        console: verbose && attach_console(),
+        file: Mutex::new(LogFile::open(paths::data_file(LOG_FILE))),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+}
+
+// The windows subsystem starts without a console, borrow the one of the launching terminal if there is one
+#[cfg(windows)]
+fn attach_console() -> bool {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    unsafe { AttachConsole(ATTACH_PARENT_PROCESS) != 0 }
+}
+
+#[cfg(not(windows))]
+fn attach_console() -> bool {
+    true
+}
@@ -2,18 +2,109 @@
 #[global_allocator]
 static ALLOC: std::alloc::System = std::alloc::System;
 
+mod audio;
+mod crash;
+mod discord;
+mod fonts;
+mod format;
+mod gamepad;
+mod history;
+mod hooks;
+mod instance;
+mod logging;
+mod mqtt;
+mod overlay;
+mod paths;
+mod plan;
+mod quotes;
+mod share;
+mod shortcuts;
+mod sounds;
+mod speech;
+mod status_output;
+mod sync;
+mod update_check;
+mod url_scheme;
+
+use chrono::Datelike;
 use eframe::egui;
-use rodio::{Decoder, OutputStream, Sink};
-use std::time::{Duration, Instant};
+use rodio::Source;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use audio::{AudioError, AudioOut, Channel, Sound};
+use discord::{DiscordPresence, PresenceStatus};
+use gamepad::{GamepadInput, PadCommand};
+use history::{History, SessionRecord, HISTORY_FILE};
+use hooks::HookRunner;
+use instance::{InstanceGuard, Startup};
+use mqtt::{LinkState, MqttConfig, MqttPublisher};
+use overlay::OverlayWriter;
+use plan::{Plan, PlanRound};
+use share::SharedConfig;
+use shortcuts::{Action, Bindings, Scope, SHORTCUTS};
+use sounds::Cue;
+use speech::Speaker;
+use status_output::StatusOutput;
+use sync::{SyncFollower, SyncLeader, SyncPacket};
+use update_check::{Release, UpdateChecker};
 
 const FANFARE_STAR: &[u8] = include_bytes!("../star.png");
-const WORK_FINISH_AUDIO: &[u8] = include_bytes!("../work_finish.mp3");
-const REST_FINISH_AUDIO: &[u8] = include_bytes!("../rest_finish.mp3");
-const COMPLETE_FINISH_AUDIO: &[u8] = include_bytes!("../complete_finish.mp3");
+// Same width for every digit, so the countdown doesn't shift sideways each second
+const COUNTDOWN_FONT: &[u8] = include_bytes!("../fonts/DejaVuSansMono-Bold.ttf");
+const COUNTDOWN_FAMILY: &str = "countdown";
+const FALLBACK_FONT: &str = "fallback";
+
+const UI_STATE_FILE: &str = "ui_state.ron";
+const UI_STATE_KEY: &str = "ui_state";
+const SETTINGS_FILE: &str = "settings.json";
+const SESSION_SNAPSHOT_FILE: &str = "session.json";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const SNAPSHOT_MAX_AGE_SECS: u64 = 60 * 60;
+const UPDATE_CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const COMPLETION_REPEAT_GAP: Duration = Duration::from_secs(1);
+const MAX_ADJUSTED_SECS: u64 = 60 * 60;
+const COUNTDOWN_BEEP: Duration = Duration::from_millis(120);
+const AUTO_RESUME_WARNING_SECS: u64 = 3;
+const SPEECH_DELAY: Duration = Duration::from_millis(1500); // Lets the phase cue finish first
+const TAKEOVER_SECS: u64 = 5;
+const CURSOR_HIDE_AFTER: Duration = Duration::from_secs(3);
+const MAX_IDLE_DIM_MINUTES: u64 = 120;
+const DIMMED_TEXT: egui::Color32 = egui::Color32::from_gray(70);
+const NOTICE_DURATION: Duration = Duration::from_secs(5);
+const MAX_PHASE_SECS: u64 = 60 * 60; // Long enough for Pomodoro style intervals
+const STOPWATCH_MAX_SECS: u64 = 24 * 60 * 60; // A forgotten stopwatch session ends here
+const MAX_LEAD_UP_SECS: u64 = 60; // Time to walk over to the mat
+const MAX_LAPS: u32 = 10;
+const MAX_SESSION_WARNING_MINUTES: u64 = 4 * 60;
+const TOUCH_BUTTON_HEIGHT: f32 = 64.0;
+const NARROW_WIDTH: f32 = 420.0; // Below this the sliders get their labels above them
+const NARROW_FONT_SCALE: f32 = 0.75;
+const NARROW_FIELD_ROOM: f32 = 80.0; // Next to a narrow slider, for the typed field
+const WIDE_WIDTH: f32 = 960.0; // Above this the controls keep to a column in the middle
+const COLUMN_WIDTH: f32 = 720.0;
+const WIDE_COUNTDOWN_SHARE: f32 = 0.12; // Of the window height
+const MIN_WINDOW_SIZE: [f32; 2] = [240.0, 320.0];
+const UNDO_LIMIT: usize = 20;
+const RECENT_CONFIGS: usize = 3;
+const UNDO_MERGE: Duration = Duration::from_secs(1); // Saves this close together are one change, e.g. a slider drag
+const NUDGE_SECS: u64 = 5; // Per wheel notch, track click or stepper tap on a duration, without a snap step
+const WHEEL_NOTCH: f32 = 50.0; // Points of scrolling egui reports for one mouse wheel notch
+const REST_PULSE_SECS: u64 = 5;
+const REST_COLOR: egui::Color32 = egui::Color32::from_rgb(0x38, 0x77, 0xA2);
+const MAX_BACKGROUND_SIZE: u32 = 4096;
+const PAUSE_TREND_WEEKS: u64 = 4;
+const HEATMAP_WEEKS: u64 = 53; // A full year, whatever weekday it starts on
+const HEATMAP_CELL: f32 = 10.0;
+const HEATMAP_GAP: f32 = 2.0;
+const GOAL_COLOR: egui::Color32 = egui::Color32::from_rgb(0x3B, 0xA4, 0x58);
+const MAX_EXERCISE_IMAGE_SIZE: u32 = 512; // Larger demo images are scaled down on load
+const EXERCISE_IMAGE_DISPLAY_SIZE: f32 = 200.0;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 enum TimerState {
     Idle,
     LeadUp,
@@ -24,12 +115,200 @@ enum TimerState {
     PausedLeadUp,
 }
 
+impl TimerState {
+    fn paused(self) -> Self {
+        match self {
+            TimerState::LeadUp => TimerState::PausedLeadUp,
+            TimerState::Workout => TimerState::PausedWorkout,
+            TimerState::Rest => TimerState::PausedRest,
+            other => other,
+        }
+    }
+
+    fn phase_key(self) -> &'static str {
+        match self {
+            TimerState::Idle => "idle",
+            TimerState::LeadUp | TimerState::PausedLeadUp => "lead_up",
+            TimerState::Workout | TimerState::PausedWorkout => "work",
+            TimerState::Rest | TimerState::PausedRest => "rest",
+        }
+    }
+
+    // Short upper-case phase name for status outputs
+    fn status_name(self) -> &'static str {
+        match self {
+            _ if self.is_paused() => "PAUSED",
+            TimerState::Idle => "IDLE",
+            TimerState::LeadUp => "GET READY",
+            TimerState::Workout => "WORK",
+            _ => "REST",
+        }
+    }
+
+    fn resumed(self) -> Self {
+        match self {
+            TimerState::PausedLeadUp => TimerState::LeadUp,
+            TimerState::PausedWorkout => TimerState::Workout,
+            TimerState::PausedRest => TimerState::Rest,
+            other => other,
+        }
+    }
+
+    fn is_paused(self) -> bool {
+        matches!(self, TimerState::PausedLeadUp | TimerState::PausedWorkout | TimerState::PausedRest)
+    }
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            TimerState::LeadUp | TimerState::PausedLeadUp => egui::Color32::from_rgb(0xFF, 0xA5, 0x00), // Orange
+            TimerState::Workout | TimerState::PausedWorkout => egui::Color32::from_rgb(0x3B, 0xA4, 0x58), // Green
+            TimerState::Rest | TimerState::PausedRest => REST_COLOR, // Blue
+            TimerState::Idle => egui::Color32::from_rgb(0x3D, 0x3D, 0x3D), // Gray
+        }
+    }
+
+    // The idle gray would be a dark block on the light theme, the phase colours read on both
+    fn color_on(self, dark_mode: bool) -> egui::Color32 {
+        match self {
+            TimerState::Idle if !dark_mode => egui::Color32::from_rgb(0xC8, 0xC8, 0xC8),
+            _ => self.color(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum Theme {
+    System, // Follows the OS, also when it switches while the timer runs
+    Dark,
+    Light,
+}
+
+impl Theme {
+    fn preference(self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Light => egui::ThemePreference::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum ProgressDirection {
+    FillUp,
+    DrainDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum TimerMode {
+    Intervals,
+    Pomodoro, // Work and rest become focus and break, with a long break every few rounds
+    Stopwatch, // Counts up as a single work phase until Finish, with laps
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum SyncMode {
+    Off,
+    Leader, // Broadcasts its timer to the local network
+    Follower, // Shows the leader's timer instead of its own
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum GoalKind {
+    Sessions,
+    WorkMinutes,
+}
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)] // Fields missing from older settings files fall back to defaults
 struct Settings {
     workout_duration: u64,
     rest_duration: u64,
     rounds: u32,
-    lead_up_duration: u32,
+    work_rest_ratio: Option<[u64; 2]>, // Rest follows the workout duration, until rest is set by hand
+    lead_up_duration: u64,
+    repeat_complete_until_dismissed: bool,
+    show_completion_message: bool,
+    completion_message: String, // {rounds} and {time} are filled in
+    rest_quotes: bool, // A different quote in each rest phase
+    quotes_file: String, // One quote per line, added to the built-in ones
+    rest_extension: u64,
+    battery_saver: bool,
+    idle_dim_minutes: u64, // Idle and untouched this long, the panel dims down to the clock. 0 never dims.
+    start_minimized: bool,
+    overlay_file: String,
+    overlay_json: bool,
+    hooks_enabled: bool,
+    work_start_command: String,
+    rest_start_command: String,
+    complete_command: String,
+    discord_presence: bool,
+    mqtt_enabled: bool,
+    mqtt_broker: String,
+    mqtt_username: String,
+    mqtt_password: String, // Stored as plain text like the rest of the settings
+    mqtt_topic: String, // Prefix, the state goes to <prefix>/state
+    progress_direction: ProgressDirection,
+    session_progress: bool, // Whole session bar with round ticks above the phase bar
+    count_rounds_down: bool, // "3 rounds left" instead of "Round 7/10", display only
+    status_output: String,
+    reduced_motion: bool,
+    theme: Theme,
+    countdown_font: bool, // Monospaced digits for the countdown, off uses the default font
+    fallback_font: String, // For CJK and other scripts, empty looks for a system font
+    check_for_updates: bool, // Opt-in, asks GitHub at most once a day at startup
+    gamepad: bool,
+    touch_mode: bool, // Big buttons and steppers instead of sliders
+    show_clock: bool, // Time of day in the top right corner
+    clock_24h: bool,
+    clock_seconds: bool,
+    key_bindings: Bindings, // Only the remapped shortcuts
+    sync_mode: SyncMode,
+    sync_port: u16, // UDP, the same on the leader and its followers
+    last_update_check: u64, // Unix seconds
+    second_display: bool, // Extra borderless window with only the big timer, e.g. for a TV
+    second_display_fullscreen: bool,
+    background_image: String, // Empty for the plain background
+    background_dim: f32, // 0 shows the image as is, 1 hides it completely
+    tag: String,
+    weekly_goal: u32, // 0 disables the goal
+    weekly_goal_kind: GoalKind,
+    week_start: chrono::Weekday,
+    snap_step: u64, // Slider step for the workout and rest durations
+    sound_pack: String,
+    volume: u32, // Percent, multiplied with the per-sound volumes below
+    cue_volume: u32,
+    beep_volume: u32, // Countdown, pre-alert and other short tones
+    complete_volume: u32,
+    rest_finish_audio: String, // Custom files for the work start, work end and completion cues,
+    work_finish_audio: String, // empty ones use the sound pack
+    complete_audio: String,
+    announce_rounds: bool, // "Round N" before the work-start cue, from the clips in voice_dir
+    speak_exercises: bool, // Round names through the OS text-to-speech
+    voice_dir: String,
+    countdown_beeps: bool, // 3-2-1 beeps, rising into work and falling into rest
+    countdown_base_freq: f32,
+    body_weight_kg: f32, // 0 means unset, no calorie estimate is shown
+    intensity_met: f32,
+    auto_resume_after: u64, // Seconds, 0 never resumes on its own
+    pre_alert_seconds: u32, // 0 disables the cue
+    switch_sides: bool, // Cue at switch_split_percent of each work phase, for unilateral exercises
+    switch_split_percent: u32,
+    switch_flash: bool,
+    plan_file: String, // Last loaded plan, for quick reloading
+    history_backup_file: String,
+    mode: TimerMode,
+    long_break_every: u32, // Pomodoro only, 0 never takes a long break
+    long_break_duration: u64,
+    confirm_stop: bool, // Ask before Stop throws away a session past its first round
+    session_length_warning_minutes: u64, // Ask before starting anything longer, 0 never asks
+    manual_advance: bool, // Work and rest count into overtime until Next is pressed
+    use_round_list: bool,
+    round_list: Vec<PlanRound>, // Edited in the app, used when no plan file is loaded
+    use_stations: bool, // Circuit: each round at the next station, a rotate break after every lap
+    stations: Vec<String>,
+    laps: u32, // Times through all stations
+    rotate_break: u64, // Instead of the rest after a lap's last station
 }
 
 impl Default for Settings {
@@ -38,45 +317,378 @@ impl Default for Settings {
             workout_duration: 60,
             rest_duration: 45,
             rounds: 10,
+            work_rest_ratio: None,
             lead_up_duration: 5,
+            repeat_complete_until_dismissed: false,
+            show_completion_message: true,
+            completion_message: "Workout complete! {rounds} rounds in {time}".to_string(),
+            rest_quotes: false,
+            quotes_file: String::new(),
+            rest_extension: 30,
+            battery_saver: false,
+            idle_dim_minutes: 0,
+            start_minimized: false,
+            overlay_file: String::new(),
+            overlay_json: false,
+            hooks_enabled: false,
+            work_start_command: String::new(),
+            rest_start_command: String::new(),
+            complete_command: String::new(),
+            discord_presence: false,
+            mqtt_enabled: false,
+            mqtt_broker: "mqtt://localhost:1883".to_string(),
+            mqtt_username: String::new(),
+            mqtt_password: String::new(),
+            mqtt_topic: "interval_timer".to_string(),
+            progress_direction: ProgressDirection::FillUp,
+            session_progress: false,
+            count_rounds_down: false,
+            status_output: String::new(),
+            reduced_motion: false,
+            theme: Theme::System,
+            fallback_font: String::new(),
+            countdown_font: true,
+            check_for_updates: false,
+            gamepad: false,
+            touch_mode: false,
+            show_clock: false,
+            clock_24h: true,
+            clock_seconds: false,
+            key_bindings: Bindings::new(),
+            sync_mode: SyncMode::Off,
+            sync_port: 47392,
+            last_update_check: 0,
+            second_display: false,
+            second_display_fullscreen: true,
+            background_image: String::new(),
+            background_dim: 0.6,
+            tag: String::new(),
+            weekly_goal: 0,
+            weekly_goal_kind: GoalKind::Sessions,
+            week_start: chrono::Weekday::Mon,
+            snap_step: 1,
+            sound_pack: sounds::SOUND_PACKS[0].name.to_string(),
+            volume: 100,
+            cue_volume: 100,
+            beep_volume: 100,
+            complete_volume: 100,
+            rest_finish_audio: String::new(),
+            work_finish_audio: String::new(),
+            complete_audio: String::new(),
+            announce_rounds: false,
+            speak_exercises: false,
+            voice_dir: String::new(),
+            countdown_beeps: false,
+            countdown_base_freq: 660.0,
+            body_weight_kg: 0.0,
+            intensity_met: 6.0,
+            auto_resume_after: 0,
+            pre_alert_seconds: 10,
+            switch_sides: false,
+            switch_split_percent: 50,
+            switch_flash: true,
+            plan_file: String::new(),
+            history_backup_file: String::new(),
+            mode: TimerMode::Intervals,
+            long_break_every: 4,
+            long_break_duration: 15 * 60,
+            confirm_stop: true,
+            session_length_warning_minutes: 0,
+            manual_advance: false,
+            use_round_list: false,
+            round_list: Vec::new(),
+            use_stations: false,
+            stations: Vec::new(),
+            laps: 3,
+            rotate_break: 120,
         }
     }
 }
 
 impl Settings {
-    fn load_from_file() -> Self {
-        if let Ok(data) = fs::read_to_string("settings.json") {
-            serde_json::from_str(&data).unwrap_or_else(|_| {
-                let default_settings = Self::default();
-                default_settings.save_to_file(); // Save defaults if file is corrupted
-                default_settings
+    // Also returns a notice for the user when the file was there but unreadable
+    fn load_from_file(path: &Path) -> (Self, Option<String>) {
+        let mut notice = None;
+        let mut settings = if let Ok(data) = fs::read_to_string(path) {
+            log::info!("Loading settings from {}", path.display());
+            serde_json::from_str(&data).unwrap_or_else(|err| {
+                log::warn!("Settings file is unreadable ({}), using defaults", err);
+                notice = Some(Self::set_aside_corrupt(path));
+                Self::default()
             })
         } else {
+            log::info!("No settings at {}, using defaults", path.display());
             let default_settings = Self::default();
-            default_settings.save_to_file(); // Save defaults if file doesn't exist
+            default_settings.save_to_file(path); // Save defaults if file doesn't exist
             default_settings
+        };
+        settings.sanitize();
+        (settings, notice)
+    }
+
+    // Moves an unreadable settings file out of the way so the defaults never overwrite it, then
+    // saves the defaults. If it can't be moved it stays as it is and nothing is written.
+    fn set_aside_corrupt(path: &Path) -> String {
+        let backup = path.with_file_name(format!(
+            "{}.corrupt-{}",
+            path.file_name().unwrap_or(SETTINGS_FILE.as_ref()).to_string_lossy(),
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        match fs::rename(path, &backup) {
+            Ok(()) => {
+                Self::default().save_to_file(path);
+                format!("Your settings file was unreadable, so the defaults are in use. A backup was kept at {}", backup.display())
+            }
+            Err(err) => {
+                log::error!("Could not back up {}: {}", path.display(), err);
+                format!(
+                    "Your settings file was unreadable, so the defaults are in use. It couldn't be backed up ({}), \
+                     copy {} somewhere safe before changing any setting",
+                    err,
+                    path.display()
+                )
+            }
+        }
+    }
+
+    // For reloading after an outside edit: a half-saved or broken file keeps the current settings
+    fn reload_from_file(path: &Path) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut settings: Settings = serde_json::from_str(&data).map_err(|err| err.to_string())?;
+        settings.sanitize();
+        Ok(settings)
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    // Hand-edited files can hold values the sliders never produce
+    fn sanitize(&mut self) {
+        self.workout_duration = self.workout_duration.clamp(2, MAX_PHASE_SECS);
+        self.rest_duration = self.rest_duration.clamp(2, MAX_PHASE_SECS);
+        self.rounds = self.rounds.clamp(1, 50);
+        for volume in [&mut self.volume, &mut self.cue_volume, &mut self.beep_volume, &mut self.complete_volume] {
+            *volume = (*volume).min(100);
+        }
+        self.lead_up_duration = self.lead_up_duration.min(MAX_LEAD_UP_SECS);
+        self.session_length_warning_minutes = self.session_length_warning_minutes.min(MAX_SESSION_WARNING_MINUTES);
+        self.idle_dim_minutes = self.idle_dim_minutes.min(MAX_IDLE_DIM_MINUTES);
+        self.work_rest_ratio = self.work_rest_ratio.filter(|[work, rest]| *work > 0 && *rest > 0);
+        self.rest_extension = self.rest_extension.clamp(5, 120);
+        self.snap_step = self.snap_step.max(1);
+        if !self.countdown_base_freq.is_finite() {
+            self.countdown_base_freq = 660.0;
+        }
+        self.countdown_base_freq = self.countdown_base_freq.clamp(200.0, 2000.0);
+        if !self.body_weight_kg.is_finite() || self.body_weight_kg < 0.0 {
+            self.body_weight_kg = 0.0;
+        }
+        if !self.intensity_met.is_finite() {
+            self.intensity_met = 6.0;
+        }
+        self.intensity_met = self.intensity_met.clamp(1.0, 20.0);
+        self.long_break_duration = self.long_break_duration.clamp(2, MAX_PHASE_SECS);
+        self.laps = self.laps.clamp(1, MAX_LAPS);
+        self.rotate_break = self.rotate_break.clamp(2, MAX_PHASE_SECS);
+        self.switch_split_percent = self.switch_split_percent.clamp(10, 90);
+        if !self.background_dim.is_finite() {
+            self.background_dim = 0.6;
+        }
+        self.background_dim = self.background_dim.clamp(0.0, 1.0);
+        for round in &mut self.round_list {
+            round.work = round.work.clamp(1, MAX_PHASE_SECS);
+            round.rest = round.rest.min(MAX_PHASE_SECS);
+        }
+    }
+
+    fn save_to_file(&self, path: &Path) {
+        if let Ok(data) = serde_json::to_string_pretty(self) {
+            match fs::write(path, data) {
+                Ok(()) => log::info!("Saved settings to {}", path.display()),
+                Err(err) => log::error!("Failed to save settings to {}: {}", path.display(), err),
+            }
+        }
+    }
+}
+
+// Fraction of the phase that has passed, always within 0..=1.
+// A zero-length phase counts as already complete (1.0) instead of dividing by zero.
+fn phase_progress(remaining: u64, duration: u64) -> f32 {
+    if duration == 0 {
+        return 1.0;
+    }
+    (1.0 - remaining as f32 / duration as f32).clamp(0.0, 1.0)
+}
+
+// MET formula over the work time only, None while the body weight is unset
+fn estimate_kcal(met: f32, weight_kg: f32, work_secs: u64) -> Option<f32> {
+    (weight_kg > 0.0).then(|| met * weight_kg * work_secs as f32 / 3600.0)
+}
+
+const RATIO_PRESETS: [[u64; 2]; 4] = [[2, 1], [1, 1], [1, 2], [3, 1]];
+const MET_PRESETS: [(&str, f32); 3] = [("Light", 3.5), ("Moderate", 6.0), ("Vigorous", 8.0)];
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// How the window was left, kept through eframe's storage (ui_state.ron) together with the window
+// size and the open sections, so settings.json only holds the timer configuration
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct UiState {
+    history_filter: Option<String>,
+    second_display_pos: Option<[f32; 2]>, // Where it was last closed, which also picks the monitor
+    recent_configs: Vec<SharedConfig>, // The last few started with, newest first
+}
+
+// Small snapshot of a running session so it can be offered again after the app gets killed
+#[derive(Serialize, Deserialize)]
+struct SessionSnapshot {
+    state: TimerState,
+    current_round: u32,
+    rounds: u32,
+    phase_duration: u64,
+    remaining_time: u64,
+    saved_at: u64,
+    // Session totals so far, 0 in snapshots from older versions
+    #[serde(default)]
+    elapsed_secs: u64, // Since Start, not counting the time the app was closed
+    #[serde(default)]
+    work_secs: u64,
+    #[serde(default)]
+    rest_secs: u64,
+    #[serde(default)]
+    pauses: u32,
+    #[serde(default)]
+    paused_secs: u64,
+    #[serde(default)]
+    phase_offset_secs: i64,
+}
+
+impl SessionSnapshot {
+    fn load_recent() -> Option<Self> {
+        let data = fs::read_to_string(paths::data_file(SESSION_SNAPSHOT_FILE)).ok()?;
+        let snapshot: Self = serde_json::from_str(&data).ok()?;
+        if snapshot.state == TimerState::Idle
+            || unix_now().saturating_sub(snapshot.saved_at) > SNAPSHOT_MAX_AGE_SECS
+        {
+            Self::delete(); // Stale or useless snapshot, nothing to offer
+            return None;
         }
+        Some(snapshot)
     }
 
     fn save_to_file(&self) {
         if let Ok(data) = serde_json::to_string_pretty(self) {
-            let _ = fs::write("settings.json", data);
+            let _ = fs::write(paths::data_file(SESSION_SNAPSHOT_FILE), data);
         }
     }
+
+    fn delete() {
+        let _ = fs::remove_file(paths::data_file(SESSION_SNAPSHOT_FILE));
+    }
 }
 
 struct WorkoutTimer {
-    workout_duration: u64,
-    rest_duration: u64,
-    rounds: u32,
+    settings: Settings,
     current_round: u32,
+    session_rounds: u32, // Round target of the running session, may grow past settings.rounds
     remaining_time: u64,
-    lead_up_duration: u32,
+    phase_duration: u64,
     start_time: Option<Instant>,
+    paused_remaining: Option<Duration>, // Exact time left in the paused phase
     fanfare_start_time: Option<Instant>,
     state: TimerState,
-    sound_sink: Option<Sink>,
-    _stream: Option<OutputStream>,
+    audio: AudioOut,
+    speech: Speaker,
+    speech_due: Option<(Instant, String)>, // Said once the phase cue is over
+    last_beep: Option<u64>,
+    pre_alert_fired: bool, // For the current phase, survives pause and resume
+    switch_fired: bool, // Like pre_alert_fired
+    switch_flash_until: Option<Instant>,
+    exercise_images: std::collections::HashMap<String, Result<egui::TextureHandle, String>>,
+    rest_quote: Option<String>, // Picked when a rest phase starts, kept until it ends
+    quote_index: usize,
+    advance_requested: bool, // Next was pressed, lets a phase held by manual advance end
+    phase_overtime: u64, // Overtime of the current phase from before a pause
+    phase_offset_secs: i64, // Time spent in the phase that its clock lost or gained by restarts and adjustments
+    last_overtime_beep: u64,
+    session_overtime_secs: u64,
+    laps: Vec<Duration>, // Splits, each measured from the previous lap
+    audio_error: Option<String>,
+    custom_sound_errors: [Option<String>; 3], // By Cue::slot
+    cue_decode_errors: [Option<String>; 3], // By Cue::slot, beeps play instead until the sound decodes again
+    keyboard_navigation: bool,
+    confirming_stop: bool,
+    confirming_start: bool, // The session is longer than the warning limit
+    preview_speed: u32, // Times real time, 1 outside preview. Never saved, a restart is back to 1.
+    stop_prompt_shown: bool, // Enter from the Stop button itself must not confirm right away
+    settings_path: PathBuf, // The data dir's settings file, a temporary one in tests
+    settings_mtime: Option<SystemTime>,
+    saved_settings: String, // The settings as last saved, what a change gets undone to
+    undo: Vec<String>, // Serialized settings, oldest first
+    redo: Vec<String>,
+    last_undo_push: Option<Instant>,
+    last_settings_check: Instant,
+    settings_changed_on_disk: bool, // Reload waits for the session to end
+    plan: Option<Plan>, // Replaces the workout, rest and rounds sliders while loaded
+    plan_error: Option<String>,
+    dropped_plan: Option<String>, // Dropped during a session, loaded once it ends
+    pasted_config: Option<SharedConfig>, // Waiting for confirmation
+    share_message: Option<String>,
+    notice: Option<(String, Instant)>, // Short message at the top, gone after a few seconds
+    show_keyboard_help: bool,
+    palette_query: Option<String>, // The command palette is open
+    palette_selected: usize,
+    crash_report: Option<PathBuf>, // Written when the previous run panicked
+    settings_notice: Option<String>, // The settings file was unreadable at startup
+    history_notice: Option<String>, // Same for the history file
+    update_checker: UpdateChecker,
+    sync_leader: SyncLeader,
+    sync_follower: Option<SyncFollower>,
+    gamepad: Option<GamepadInput>, // Running while the setting is on
+    touch_style: bool, // Whether the style is currently set up for touch
+    font_error: Option<String>, // The fallback font file couldn't be used
+    capturing_key: Option<&'static str>, // Binding waiting for its new key
+    key_binding_error: Option<String>,
+    sync_config: Option<(SyncMode, u16)>, // What the sync sockets were set up for
+    sync_error: Option<String>,
+    available_update: Option<Release>,
+    last_input: Instant, // Mouse movement, touch or key press, for hiding the cursor and dimming
+    idle_since: Instant, // Last return to Idle, or the launch
+    last_snapshot: Option<Instant>,
+    pending_resume: Option<SessionSnapshot>,
+    completion_alarm: bool,
+    star_texture: Option<egui::TextureHandle>,
+    second_display_at: Option<egui::Pos2>, // Current position of the open display window
+    background: Option<egui::TextureHandle>,
+    background_path: String, // Path the texture was loaded from, reloads when the setting changes
+    background_error: Option<String>,
+    minimize_on_start: bool,
+    overlay: OverlayWriter,
+    hooks: HookRunner,
+    discord: DiscordPresence,
+    mqtt: MqttPublisher,
+    mqtt_config: Option<MqttConfig>, // What the publisher runs with, None while off
+    instance: Option<InstanceGuard>,
+    status_output: StatusOutput,
+    status_output_override: Option<String>,
+    history: History,
+    history_path: PathBuf, // Like settings_path, a temporary one in tests
+    ui: UiState,
+    dark_mode: bool, // Of the visuals in use, which with Theme::System is the OS's choice
+    backup_message: Option<String>,
+    session_work_secs: u64,
+    session_rest_secs: u64, // Rest actually taken, after skips, extensions and overtime
+    goal_reached_fanfare: bool,
+    session_started_at: Option<Instant>,
+    paused_at: Option<Instant>,
+    session_paused_secs: u64,
+    auto_resume_warned: bool,
+    session_pauses: u32,
+    last_session: Option<SessionRecord>, // Summary shown after a completed session
 }
 
 impl Default for WorkoutTimer {
@@ -87,319 +699,4558 @@ impl Default for WorkoutTimer {
 
 impl WorkoutTimer {
     fn new() -> Self {
-        let settings = Settings::load_from_file();
-        let stream = OutputStream::try_default().ok().map(|(s, _)| s);
-
-        Self {
-            workout_duration: settings.workout_duration,
-            rest_duration: settings.rest_duration,
-            rounds: settings.rounds,
-            lead_up_duration: settings.lead_up_duration,
+        let settings_path = paths::data_file(SETTINGS_FILE);
+        let (settings, settings_notice) = Settings::load_from_file(&settings_path);
+        let mut timer = Self::with_settings(settings, settings_path);
+        timer.settings_notice = settings_notice;
+        timer.minimize_on_start = timer.settings.start_minimized || std::env::args().any(|arg| arg == "--minimized");
+        timer.crash_report = crash::take_pending_report();
+        timer.pending_resume = SessionSnapshot::load_recent();
+        // --status-output <path> applies to this run only and is not saved
+        timer.status_output_override = std::env::args().skip_while(|arg| arg != "--status-output").nth(1);
+        (timer.history, timer.history_notice) = History::load_from_file(&timer.history_path);
+        if let Some(path) = std::env::args().skip_while(|arg| arg != "--plan").nth(1) {
+            timer.load_plan(path);
+        }
+        timer.check_custom_sounds(); // A moved or deleted file shows up before the first cue
+        let now = unix_now();
+        if timer.settings.check_for_updates
+            && now.saturating_sub(timer.settings.last_update_check) >= UPDATE_CHECK_INTERVAL_SECS
+        {
+            timer.update_checker.start();
+            timer.settings.last_update_check = now;
+            timer.save_settings();
+        }
+        timer
+    }
+
+    // Idle with the given settings. Apart from the settings file's time stamp nothing is read,
+    // the history, crash report and command line are up to new().
+    fn with_settings(settings: Settings, settings_path: PathBuf) -> Self {
+        let mut timer = Self {
+            settings,
             current_round: 0,
+            session_rounds: 0,
             remaining_time: 0,
+            phase_duration: 0,
             start_time: None,
+            paused_remaining: None,
             state: TimerState::Idle,
-            sound_sink: None,
-            _stream: stream,
+            audio: AudioOut::default(),
+            speech: Speaker::default(),
+            speech_due: None,
+            last_beep: None,
+            pre_alert_fired: false,
+            switch_fired: false,
+            switch_flash_until: None,
+            exercise_images: Default::default(),
+            rest_quote: None,
+            quote_index: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as usize), // Not the same first quote every launch
+            advance_requested: false,
+            phase_overtime: 0,
+            phase_offset_secs: 0,
+            last_overtime_beep: 0,
+            session_overtime_secs: 0,
+            laps: Vec::new(),
+            audio_error: None,
+            custom_sound_errors: Default::default(),
+            cue_decode_errors: Default::default(),
+            keyboard_navigation: false,
+            confirming_stop: false,
+            confirming_start: false,
+            preview_speed: 1,
+            stop_prompt_shown: false,
+            settings_mtime: Settings::modified_time(&settings_path),
+            settings_path,
+            saved_settings: String::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_undo_push: None,
+            last_settings_check: Instant::now(),
+            settings_changed_on_disk: false,
+            plan: None,
+            plan_error: None,
+            dropped_plan: None,
+            pasted_config: None,
+            share_message: None,
+            notice: None,
+            show_keyboard_help: false,
+            palette_query: None,
+            palette_selected: 0,
+            crash_report: None,
+            settings_notice: None,
+            history_notice: None,
+            update_checker: UpdateChecker::default(),
+            sync_leader: SyncLeader::default(),
+            sync_follower: None,
+            gamepad: None,
+            touch_style: false,
+            font_error: None,
+            capturing_key: None,
+            key_binding_error: None,
+            sync_config: None,
+            sync_error: None,
+            available_update: None,
+            last_input: Instant::now(),
+            idle_since: Instant::now(),
             fanfare_start_time: None,
-        }
+            last_snapshot: None,
+            pending_resume: None,
+            completion_alarm: false,
+            star_texture: None,
+            second_display_at: None,
+            background: None,
+            background_path: String::new(),
+            background_error: None,
+            minimize_on_start: false,
+            overlay: OverlayWriter::default(),
+            hooks: HookRunner::default(),
+            discord: DiscordPresence::default(),
+            mqtt: MqttPublisher::default(),
+            mqtt_config: None,
+            instance: None,
+            status_output: StatusOutput::default(),
+            status_output_override: None,
+            history: History::default(),
+            history_path: paths::data_file(HISTORY_FILE),
+            ui: UiState::default(),
+            dark_mode: true,
+            backup_message: None,
+            session_work_secs: 0,
+            session_rest_secs: 0,
+            goal_reached_fanfare: false,
+            session_started_at: None,
+            paused_at: None,
+            session_paused_secs: 0,
+            auto_resume_warned: false,
+            session_pauses: 0,
+            last_session: None,
+        };
+        timer.saved_settings = timer.settings_json();
+        timer
     }
 
-    fn save_settings(&self) {
-        let settings = Settings {
-            workout_duration: self.workout_duration,
-            rest_duration: self.rest_duration,
-            rounds: self.rounds,
-            lead_up_duration: self.lead_up_duration,
-        };
-        settings.save_to_file();
+    // Picks up edits made outside the app (by hand, or synced from another machine)
+    fn check_settings_file(&mut self) {
+        if self.last_settings_check.elapsed() < SETTINGS_POLL_INTERVAL {
+            return;
+        }
+        self.last_settings_check = Instant::now();
+        let mtime = Settings::modified_time(&self.settings_path);
+        if mtime.is_none() || mtime == self.settings_mtime {
+            return;
+        }
+        if self.state != TimerState::Idle {
+            self.settings_changed_on_disk = true;
+            return;
+        }
+        self.settings_mtime = mtime;
+        self.settings_changed_on_disk = false;
+        match Settings::reload_from_file(&self.settings_path) {
+            Ok(settings) => {
+                log::info!("Settings changed on disk, reloaded");
+                self.settings = settings;
+                self.saved_settings = self.settings_json();
+            }
+            Err(err) => log::warn!("Settings changed on disk but can't be read ({}), keeping the current ones", err),
+        }
     }
 
-    fn play_sound(&mut self, is_work: bool, is_complete: bool) {
-        if let Ok((stream, stream_handle)) = OutputStream::try_default() {
-            let sink = Sink::try_new(&stream_handle).unwrap();
+    // Catches changes that never went through save_settings, e.g. values clamped on load.
+    // A file edited outside the app that hasn't been picked up yet is left alone.
+    fn flush_settings(&mut self) {
+        if Settings::modified_time(&self.settings_path) != self.settings_mtime {
+            return;
+        }
+        let on_disk = fs::read_to_string(&self.settings_path).ok();
+        if serde_json::to_string_pretty(&self.settings).ok() != on_disk {
+            self.save_settings();
+        }
+    }
 
-            let audio_data = if is_complete {
-                COMPLETE_FINISH_AUDIO
-            } else if is_work {
-                WORK_FINISH_AUDIO
-            } else {
-                REST_FINISH_AUDIO
-            };
+    fn load_plan(&mut self, path: String) {
+        match Plan::load(Path::new(&path)) {
+            Ok(plan) => {
+                log::info!("Loaded plan {} with {} rounds", path, plan.rounds.len());
+                self.plan = Some(plan);
+                self.plan_error = None;
+                self.settings.plan_file = path;
+                self.save_settings();
+            }
+            Err(err) => {
+                log::warn!("Failed to load plan: {}", err);
+                self.plan_error = Some(err);
+            }
+        }
+    }
 
-            let cursor = std::io::Cursor::new(audio_data);
-            let source = Decoder::new(cursor).unwrap();
-            sink.append(source);
-            self.sound_sink = Some(sink);
-            self._stream = Some(stream);
+    // The workout as it would run now, a loaded plan is shared as its rounds
+    // Image paths only make sense on this machine
+    fn shared_config(&self) -> SharedConfig {
+        let mut config = self.current_config();
+        for round in &mut config.round_list {
+            round.image = None;
         }
+        config
     }
 
-    fn trigger_visual_fanfare(&mut self) {
-        self.fanfare_start_time = Some(Instant::now());
+    fn current_config(&self) -> SharedConfig {
+        SharedConfig {
+            workout_duration: self.settings.workout_duration,
+            rest_duration: self.settings.rest_duration,
+            rounds: self.settings.rounds,
+            lead_up_duration: self.settings.lead_up_duration,
+            mode: self.settings.mode,
+            long_break_every: self.settings.long_break_every,
+            long_break_duration: self.settings.long_break_duration,
+            round_list: self.round_list().unwrap_or_default().to_vec(),
+            use_stations: self.settings.use_stations,
+            stations: self.settings.stations.clone(),
+            laps: self.settings.laps,
+            rotate_break: self.settings.rotate_break,
+        }
     }
 
-    fn update(&mut self) {
-        if let Some(start) = self.start_time {
-            let elapsed = start.elapsed().as_secs();
+    // At every Start, so the quick-start buttons follow what is actually used
+    fn remember_config(&mut self) {
+        let config = self.current_config();
+        self.ui.recent_configs.retain(|recent| *recent != config);
+        self.ui.recent_configs.insert(0, config);
+        self.ui.recent_configs.truncate(RECENT_CONFIGS);
+    }
 
-            match self.state {
-                TimerState::LeadUp => {
-                    // Handle lead-up phase
-                    self.remaining_time = self.lead_up_duration as u64 - elapsed;
-                    if elapsed >= self.lead_up_duration as u64 {
-                        self.state = TimerState::Workout;
-                        self.start_time = Some(Instant::now());
-                        self.remaining_time = self.workout_duration;
-                    }
-                }
-                TimerState::Workout => {
-                    self.remaining_time = self.workout_duration.saturating_sub(elapsed);
-                    if elapsed >= self.workout_duration {
-                        self.state = TimerState::Rest;
-                        self.start_time = Some(Instant::now());
-                        self.remaining_time = self.rest_duration;
-                        self.play_sound(true, false);
-                    }
+    // One button per recent config, Shift+click starts it straight away
+    fn recent_configs_ui(&mut self, ui: &mut egui::Ui) {
+        if self.ui.recent_configs.is_empty() {
+            return;
+        }
+        let current = self.current_config();
+        let mut picked = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Recent");
+            for (index, config) in self.ui.recent_configs.iter().enumerate() {
+                let chip = ui
+                    .selectable_label(*config == current, config.short_label())
+                    .on_hover_text(format!("{}\nShift+click to start right away", config.describe()));
+                if chip.clicked() {
+                    picked = Some((index, ui.input(|i| i.modifiers.shift)));
                 }
-                TimerState::Rest => {
-                    self.remaining_time = self.rest_duration.saturating_sub(elapsed);
-                    if elapsed >= self.rest_duration {
-                        if self.current_round + 1 < self.rounds {
-                            self.current_round += 1;
-                            self.state = TimerState::Workout;
-                            self.start_time = Some(Instant::now());
-                            self.remaining_time = self.workout_duration;
-                            self.play_sound(false, false);
-                        } else {
-                            self.state = TimerState::Idle;
-                            self.start_time = None;
-                            self.current_round = 0;
-                            self.play_sound(false, true);
-                            self.trigger_visual_fanfare();
-                        }
+            }
+        });
+        if let Some((index, start)) = picked {
+            let config = self.ui.recent_configs[index].clone();
+            if config != current {
+                self.apply_shared_config(config);
+            }
+            if start {
+                self.request_start();
+            }
+        }
+    }
+
+    fn apply_shared_config(&mut self, config: SharedConfig) {
+        self.settings.workout_duration = config.workout_duration;
+        self.settings.rest_duration = config.rest_duration;
+        self.settings.rounds = config.rounds;
+        self.settings.lead_up_duration = config.lead_up_duration;
+        self.settings.mode = config.mode;
+        self.settings.long_break_every = config.long_break_every;
+        self.settings.long_break_duration = config.long_break_duration;
+        self.settings.use_round_list = !config.round_list.is_empty();
+        if self.settings.use_round_list {
+            self.settings.round_list = config.round_list;
+        }
+        self.settings.use_stations = config.use_stations;
+        if self.settings.use_stations {
+            self.settings.stations = config.stations;
+            self.settings.laps = config.laps;
+            self.settings.rotate_break = config.rotate_break;
+        }
+        self.settings.sanitize(); // Shared strings are typed in and edited by people
+        self.plan = None; // Would win over the pasted rounds
+        self.save_settings();
+    }
+
+    fn share_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Copy config").on_hover_text("Durations, rounds and round list as text").clicked() {
+                ui.ctx().copy_text(share::encode(&self.shared_config()));
+                self.share_message = Some("Copied to the clipboard".to_string());
+            }
+            let idle = self.state == TimerState::Idle;
+            if ui.add_enabled(idle, egui::Button::new("Paste config")).clicked() {
+                let text = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text());
+                match text.map_err(|err| err.to_string()).and_then(|text| share::decode(&text)) {
+                    Ok(config) => {
+                        self.pasted_config = Some(config);
+                        self.share_message = None;
                     }
-                }
-                TimerState::PausedLeadUp | TimerState::PausedWorkout | TimerState::PausedRest => {
-                    // Do nothing while paused
-                }
-                TimerState::Idle => {
-                    // Do nothing while idle
+                    Err(err) => self.share_message = Some(err),
                 }
             }
+        });
+        if let Some(message) = &self.share_message {
+            ui.label(message);
         }
     }
-}
 
-impl eframe::App for WorkoutTimer {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.update();
+    fn confirm_paste_ui(&mut self, ctx: &egui::Context) {
+        let Some(config) = &self.pasted_config else {
+            return;
+        };
+        let mut apply = false;
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("confirm_paste")).show(ctx, |ui| {
+            ui.label("Use this config?");
+            ui.label(config.describe());
+            for round in &config.round_list {
+                ui.weak(format!("{}: {}s work, {}s rest", round.name, round.work, round.rest));
+            }
+            ui.horizontal(|ui| {
+                apply = ui.button("Apply").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        if apply && let Some(config) = self.pasted_config.take() {
+            self.apply_shared_config(config);
+            self.share_message = Some("Config applied".to_string());
+        } else if cancel || modal.should_close() {
+            self.pasted_config = None;
+        }
+    }
 
-        // Define custom text styles
-        let mut style = (*ctx.style()).clone();
-        style.text_styles = [
-            (egui::TextStyle::Heading, egui::FontId::new(24.0, egui::FontFamily::Proportional)),
-            (egui::TextStyle::Body, egui::FontId::new(18.0, egui::FontFamily::Proportional)),
-            (egui::TextStyle::Button, egui::FontId::new(30.0, egui::FontFamily::Proportional)),
-        ]
-        .into();
-        
-        // Adjust sizes for sliders and progress bars
-        style.spacing.slider_width = 240.0; // Increase slider width
-        style.spacing.item_spacing.y = 10.0; // Increase vertical spacing between items
-        style.spacing.interact_size.y = 30.0; // Increase height of interactive elements (including sliders)
+    // Files dropped onto the window go through the same loading as the Load button
+    fn handle_dropped_files(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.iter().find_map(|file| file.path.clone()));
+        if let Some(path) = dropped {
+            let is_plan = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+            let path = path.to_string_lossy().into_owned();
+            if !is_plan {
+                self.show_notice(format!("{} is not a .toml plan file", path));
+            } else if self.state == TimerState::Idle {
+                self.load_dropped_plan(path);
+            } else {
+                self.show_notice("The plan will load after the current session".to_string());
+                self.dropped_plan = Some(path);
+            }
+        }
+        if self.state == TimerState::Idle && let Some(path) = self.dropped_plan.take() {
+            self.load_dropped_plan(path);
+        }
+    }
 
-        ctx.set_style(style);
-
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Workout Interval Timer");
-
-            // Check if fanfare is active
-            if let Some(start_time) = self.fanfare_start_time {
-                let elapsed = start_time.elapsed().as_secs_f32();
-                if elapsed < 2.0 {
-                    // Display fanfare message
-                    ui.vertical(|ui| {
-                        ui.label(format!("Congratulations, you completed {} rounds!", self.rounds));
-
-                        // Display three spinning stars
-                        let angle = elapsed * 2.0 * std::f32::consts::PI; // Rotate 360 degrees per second
-                        let image = {
-                            let decoder = image::load_from_memory(FANFARE_STAR).unwrap();
-                            let rgba = decoder.to_rgba8();
-                            let size = [rgba.width() as usize, rgba.height() as usize];
-                            egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice())
-                        };
-                        let texture = ctx.load_texture("star", image, egui::TextureOptions::default());
+    fn load_dropped_plan(&mut self, path: String) {
+        self.load_plan(path);
+        let notice = match (&self.plan_error, &self.plan) {
+            (Some(err), _) => format!("Plan not loaded: {}", err),
+            (None, Some(plan)) => format!("Loaded plan with {}", format::count(plan.rounds.len() as u64, "round")),
+            (None, None) => return,
+        };
+        self.show_notice(notice);
+    }
 
-                        ui.horizontal(|ui| {
-                            for _ in 0..3 {
-                                ui.add(egui::Image::new(&texture).rotate(angle, egui::Vec2::new(0.5, 0.5)));
-                            }
-                        });
-                    });
-                } else {
-                    self.fanfare_start_time = None; // End fanfare
-                }
+    fn show_notice(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+
+    // interval-timer:// links, from the command line or forwarded by a later launch
+    fn open_link(&mut self, url: &str) {
+        let request = match url_scheme::parse(url) {
+            Ok(request) => request,
+            Err(err) => {
+                log::warn!("Ignoring link {}: {}", url, err);
+                self.show_notice(err);
+                return;
             }
+        };
+        if self.state != TimerState::Idle {
+            self.show_notice("Stop the current session to use the link".to_string());
+            return;
+        }
+        log::info!("Opening link {:?}", request);
+        if let Some(work) = request.work {
+            self.settings.workout_duration = work;
+        }
+        if let Some(rest) = request.rest {
+            self.settings.rest_duration = rest;
+        }
+        if let Some(rounds) = request.rounds {
+            self.settings.rounds = rounds;
+        }
+        if let Some(lead_up) = request.lead_up {
+            self.settings.lead_up_duration = lead_up;
+        }
+        self.settings.sanitize();
+        // The link describes a plain interval workout
+        self.settings.mode = TimerMode::Intervals;
+        self.settings.use_round_list = false;
+        self.settings.use_stations = false;
+        self.plan = None;
+        self.save_settings();
+        if request.start {
+            self.request_start();
+        }
+    }
 
-            let slider_width = ui.available_width();
+    fn notice_ui(&mut self, ui: &mut egui::Ui) {
+        match &self.notice {
+            Some((notice, at)) if at.elapsed() < NOTICE_DURATION => {
+                ui.colored_label(ui.visuals().warn_fg_color, notice);
+            }
+            Some(_) => self.notice = None,
+            None => {}
+        }
+    }
 
-            let mut changed = false;
+    fn drop_hover_ui(&self, ctx: &egui::Context) {
+        if ctx.input(|i| i.raw.hovered_files.is_empty()) {
+            return;
+        }
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("drop_hover")));
+        let rect = ctx.screen_rect();
+        painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(180));
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Drop to load plan",
+            egui::FontId::proportional(32.0),
+            egui::Color32::WHITE,
+        );
+    }
 
-            changed |= ui.add_sized(
-                [slider_width, 20.0],
-                egui::Slider::new(&mut self.workout_duration, 2..=180)
-                    .text("Workout (sec)"),
-            ).changed();
+    // Work and rest for a round, from the plan when one is loaded. Rounds added on the fly repeat the last one.
+    fn round_durations(&self, round: u32) -> (u64, u64) {
+        if self.stopwatch() {
+            return (STOPWATCH_MAX_SECS, 0);
+        }
+        match self.planned_round(round) {
+            Some(planned) => (planned.work, planned.rest),
+            None => (self.settings.workout_duration, self.settings.rest_duration),
+        }
+    }
 
-            changed |= ui.add_sized(
-                [slider_width, 20.0],
-                egui::Slider::new(&mut self.rest_duration, 2..=90)
-                    .text("Rest (sec)"),
-            ).changed();
+    // A loaded plan file comes first, then the round list from the settings
+    fn round_list(&self) -> Option<&[PlanRound]> {
+        match &self.plan {
+            Some(plan) => Some(&plan.rounds),
+            None if self.settings.use_round_list && !self.settings.round_list.is_empty() => {
+                Some(&self.settings.round_list)
+            }
+            None => None,
+        }
+    }
 
-            changed |= ui.add_sized(
-                [slider_width, 20.0],
-                egui::Slider::new(&mut self.rounds, 1..=50)
-                    .text("Rounds"),
-            ).changed();
+    fn planned_round(&self, round: u32) -> Option<&PlanRound> {
+        self.round_list().and_then(|rounds| rounds.get(round as usize).or(rounds.last()))
+    }
 
-            changed |= ui.add_sized(
-                [slider_width, 20.0],
-                egui::Slider::new(&mut self.lead_up_duration, 0..=10)
-                    .text("Lead-up (sec)"),
-            ).changed();
+    // Interval mode only, a loaded plan or the round list takes precedence
+    fn stations(&self) -> Option<&[String]> {
+        let active = self.settings.use_stations
+            && !self.settings.stations.is_empty()
+            && self.settings.mode == TimerMode::Intervals
+            && self.round_list().is_none();
+        active.then_some(self.settings.stations.as_slice())
+    }
 
-            // Save settings if any slider value changed
-            if changed {
-                self.save_settings();
-            }
+    // Station and lap of a round, both from 0
+    fn station_of(&self, round: u32) -> Option<(usize, u32)> {
+        let count = self.stations()?.len() as u32;
+        Some(((round % count) as usize, round / count))
+    }
 
-            match self.state {
-                TimerState::Idle => {
-                    ui.horizontal(|ui| {
-                        if ui.button("Start").clicked() {
-                            self.current_round = 0;
-                            self.start_time = Some(Instant::now());
-                            self.state = TimerState::LeadUp;
-                            self.remaining_time = self.lead_up_duration as u64;
-                        }
-                    });
-                }
-                TimerState::LeadUp => {
-                    ui.horizontal(|ui| {
-                        if ui.button("Pause").clicked() {
-                            self.state = TimerState::PausedLeadUp;
-                            self.start_time = None;
-                        }
-                        if ui.button("Stop").clicked() {
-                            self.state = TimerState::Idle;
-                            self.start_time = None;
-                            self.remaining_time = 0;
-                            self.current_round = 0;
-                        }
-                    });
-                }
-                TimerState::Workout | TimerState::Rest => {
-                    ui.horizontal(|ui| {
-                        if ui.button("Pause").clicked() {
-                            self.state = match self.state {
-                                TimerState::Workout => TimerState::PausedWorkout,
-                                TimerState::Rest => TimerState::PausedRest,
-                                _ => unreachable!(),
-                            };
-                            self.start_time = None;
-                        }
-                        if ui.button("Stop").clicked() {
-                            self.state = TimerState::Idle;
-                            self.start_time = None;
-                            self.remaining_time = 0;
-                            self.current_round = 0;
-                        }
-                    });
-                }
-                TimerState::PausedLeadUp | TimerState::PausedWorkout | TimerState::PausedRest => {
-                    ui.horizontal(|ui| {
-                        if ui.button("Resume").clicked() {
-                            self.start_time = Some(Instant::now() - Duration::from_secs(
-                                match self.state {
-                                    TimerState::PausedLeadUp => self.lead_up_duration as u64 - self.remaining_time,
-                                    TimerState::PausedWorkout => self.workout_duration - self.remaining_time,
-                                    TimerState::PausedRest => self.rest_duration - self.remaining_time,
-                                    _ => unreachable!(),
-                                }
-                            ));
-                            self.state = match self.state {
-                                TimerState::PausedLeadUp => TimerState::LeadUp,
-                                TimerState::PausedWorkout => TimerState::Workout,
-                                TimerState::PausedRest => TimerState::Rest,
-                                _ => unreachable!(),
-                            };
-                        }
-                        if ui.button("Stop").clicked() {
-                            self.state = TimerState::Idle;
-                            self.start_time = None;
-                            self.remaining_time = 0;
-                            self.current_round = 0;
-                        }
-                    });
-                }
-            }
+    // After the last station of every lap but the final one
+    fn is_rotation(&self, round: u32) -> bool {
+        let Some(count) = self.stations().map(|stations| stations.len() as u32) else {
+            return false;
+        };
+        (round + 1).is_multiple_of(count) && round + 1 < self.rounds_target()
+    }
 
-            ui.label(format!("Round: {}/{}", self.current_round + 1, self.rounds));
-            let state_label = format!("State: {:?}", self.state)
-                .replace("PausedLeadUp", "Paused Lead-Up")
-                .replace("PausedWorkout", "Paused Workout")
-                .replace("PausedRest", "Paused Rest");
-            ui.label(state_label);
+    // "Station: Rower (3/6), Lap 2/3"
+    fn station_label(&self) -> Option<String> {
+        let stations = self.stations()?;
+        let (station, lap) = self.station_of(self.current_round)?;
+        let laps = self.rounds_target().div_ceil(stations.len() as u32); // Rounds added on the fly can start another lap
+        Some(format!(
+            "Station: {} ({}/{}), Lap {}/{}",
+            stations[station].trim(),
+            station + 1,
+            stations.len(),
+            lap + 1,
+            laps
+        ))
+    }
 
-            // Add countdown timer
-            ui.label(format!("Time remaining: {:02}:{:02}", self.remaining_time / 60, self.remaining_time % 60));
+    // The plan's or the round list's name for the round, or else its station
+    fn round_name(&self, round: u32) -> Option<&str> {
+        match self.planned_round(round) {
+            Some(planned) => Some(planned.name.as_str()),
+            None => self.station_of(round).and_then(|(station, _)| self.stations()?.get(station)).map(String::as_str),
+        }
+    }
 
-            // Add progress bar
-            let progress = match self.state {
-                TimerState::LeadUp | TimerState::PausedLeadUp => {
-                    1.0 - (self.remaining_time as f32 / self.lead_up_duration as f32)
-                }
-                TimerState::Workout | TimerState::PausedWorkout => {
-                    1.0 - (self.remaining_time as f32 / self.workout_duration as f32)
-                }
-                TimerState::Rest | TimerState::PausedRest => {
-                    1.0 - (self.remaining_time as f32 / self.rest_duration as f32)
-                }
-                TimerState::Idle => 0.0,
-            };
+    fn pomodoro(&self) -> bool {
+        self.settings.mode == TimerMode::Pomodoro
+    }
 
-            let progress_bar = egui::ProgressBar::new(progress)
-            .show_percentage()
-            .fill(match self.state {
-                TimerState::LeadUp | TimerState::PausedLeadUp => egui::Color32::from_rgb(0xFF, 0xA5, 0x00), // Orange
-                TimerState::Workout | TimerState::PausedWorkout => egui::Color32::from_rgb(0x3B, 0xA4, 0x58), // Green
-                TimerState::Rest | TimerState::PausedRest => egui::Color32::from_rgb(0x38, 0x77, 0xA2), // Blue
-                TimerState::Idle => egui::Color32::from_rgb(0x3D, 0x3D, 0x3D), // Gray
-            });
-            
-            ui.add(progress_bar);
-        });
+    fn stopwatch(&self) -> bool {
+        self.settings.mode == TimerMode::Stopwatch
+    }
 
-        ctx.request_repaint_after(Duration::from_millis(100));
+    // Time counted up in stopwatch mode, pauses left out
+    fn stopwatch_elapsed(&self) -> Duration {
+        match self.state.resumed() {
+            TimerState::Workout if self.stopwatch() => {
+                Duration::from_millis((self.phase_duration * 1000).saturating_sub(self.remaining_ms()))
+            }
+            _ => Duration::ZERO,
+        }
     }
-}
 
-fn main() -> eframe::Result<()> {
-    let mut options = eframe::NativeOptions::default();
+    fn lap(&mut self) {
+        if !self.stopwatch() || self.state.resumed() != TimerState::Workout {
+            return;
+        }
+        let split = self.stopwatch_elapsed().saturating_sub(self.laps.iter().sum());
+        self.laps.push(split);
+    }
 
-    // Use the window_builder hook to set the initial window size
-    options.window_builder = Some(Box::new(|builder| {
-        builder
-            .with_title("Workout Timer") // Set the window title
-            .with_inner_size((450.0, 450.0)) // Set the initial window size
-    }));
+    fn laps_ui(&self, ui: &mut egui::Ui) {
+        ui.strong("Laps");
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            egui::Grid::new("laps").striped(true).show(ui, |ui| {
+                ui.weak("#");
+                ui.weak("Split");
+                ui.weak("Total");
+                ui.end_row();
+                let mut total = Duration::ZERO;
+                for (index, split) in self.laps.iter().enumerate() {
+                    total += *split;
+                    ui.label((index + 1).to_string());
+                    ui.label(format::clock(split.as_secs()));
+                    ui.label(format::clock(total.as_secs()));
+                    ui.end_row();
+                }
+            });
+        });
+    }
 
-    eframe::run_native(
+    // In Pomodoro mode every Nth break is the long one
+    fn is_long_break(&self, round: u32) -> bool {
+        let every = self.settings.long_break_every;
+        self.pomodoro() && every > 0 && (round + 1).is_multiple_of(every)
+    }
+
+    fn rest_duration(&self, round: u32) -> u64 {
+        if self.is_long_break(round) {
+            self.settings.long_break_duration
+        } else if self.is_rotation(round) {
+            self.settings.rotate_break
+        } else {
+            self.round_durations(round).1
+        }
+    }
+
+    fn phase_name(&self) -> &'static str {
+        match (self.state.resumed(), self.pomodoro()) {
+            (TimerState::Idle, _) => "Idle",
+            (TimerState::LeadUp, _) => "Lead-Up",
+            (TimerState::Workout, false) => "Workout",
+            (TimerState::Workout, true) => "Focus",
+            (_, false) if self.is_rotation(self.current_round) => "Rotate",
+            (_, false) => "Rest",
+            (_, true) if self.is_long_break(self.current_round) => "Long break",
+            (_, true) => "Break",
+        }
+    }
+
+    fn status_name(&self) -> &'static str {
+        match self.state {
+            TimerState::Workout if self.pomodoro() => "FOCUS",
+            TimerState::Rest if self.pomodoro() => "BREAK",
+            state => state.status_name(),
+        }
+    }
+
+    fn phase_color(&self) -> egui::Color32 {
+        match self.state.resumed() {
+            TimerState::Workout if self.pomodoro() => egui::Color32::from_rgb(0xD9, 0x4F, 0x3D), // Tomato
+            _ => self.state.color_on(self.dark_mode),
+        }
+    }
+
+    // Planned length of rounds first..total, the one place session lengths are worked out.
+    // A single round session has no rest.
+    fn rounds_secs(&self, first: u32, total: u32) -> u64 {
+        (first..total)
+            .map(|round| {
+                let rest = if total == 1 { 0 } else { self.rest_duration(round) };
+                self.round_durations(round).0 + rest
+            })
+            .sum()
+    }
+
+    // Planned work and rest of the running session, as configured when each phase was set up
+    fn planned_phase_secs(&self) -> (u64, u64) {
+        let total = self.session_rounds;
+        let work = (0..total).map(|round| self.round_durations(round).0).sum();
+        let rest = self.rounds_secs(0, total) - work;
+        (work, rest)
+    }
+
+    fn session_total_secs(&self) -> u64 {
+        self.settings.lead_up_duration + self.rounds_secs(0, self.planned_rounds())
+    }
+
+    // What is left of the running session: this phase, the rest of this round and all later rounds
+    fn session_remaining_secs(&self) -> u64 {
+        let round = self.current_round;
+        let total = self.session_rounds;
+        let later = self.rounds_secs(round + 1, total);
+        match self.state.resumed() {
+            TimerState::Idle => 0,
+            TimerState::LeadUp => self.remaining_time + self.rounds_secs(0, total),
+            TimerState::Workout => {
+                let rest = if total == 1 { 0 } else { self.rest_duration(round) };
+                self.remaining_time + rest + later
+            }
+            _ => self.remaining_time + later,
+        }
+    }
+
+    // Share of the running session done so far, and where each later round starts
+    fn session_progress(&self) -> (f32, Vec<f32>) {
+        let lead_up = self.settings.lead_up_duration;
+        let total = lead_up + self.rounds_secs(0, self.session_rounds);
+        if total == 0 {
+            return (0.0, Vec::new());
+        }
+        let done = total.saturating_sub(self.session_remaining_secs());
+        let ticks = (1..self.session_rounds)
+            .map(|round| (lead_up + self.rounds_secs(0, round)) as f32 / total as f32)
+            .collect();
+        (done as f32 / total as f32, ticks)
+    }
+
+    fn session_summary_text(&self) -> String {
+        if self.stopwatch() {
+            return format!("Stopwatch, counts up until Finish after {}s lead-up", self.settings.lead_up_duration);
+        }
+        let rounds = self.planned_rounds();
+        let uniform = self.round_list().is_none() && !(self.pomodoro() && self.settings.long_break_every > 0);
+        let body = if let Some(stations) = self.stations() {
+            let rotate = if self.settings.laps > 1 { format!(", {}s to rotate", self.settings.rotate_break) } else { String::new() };
+            format!(
+                "{} × {} × ({}s work + {}s rest){}",
+                format::count(self.settings.laps as u64, "lap"),
+                format::count(stations.len() as u64, "station"),
+                self.settings.workout_duration,
+                self.settings.rest_duration,
+                rotate
+            )
+        } else if uniform && rounds > 1 {
+            format!(
+                "{} × ({}s work + {}s rest)",
+                format::count(rounds as u64, "round"),
+                self.settings.workout_duration,
+                self.settings.rest_duration
+            )
+        } else if uniform {
+            format!("1 round × {}s work", self.settings.workout_duration)
+        } else {
+            format!("{} as planned", format::count(rounds as u64, "round"))
+        };
+        format!(
+            "{} + {}s lead-up = {} total",
+            body,
+            self.settings.lead_up_duration,
+            format::clock(self.session_total_secs())
+        )
+    }
+
+    fn planned_rounds(&self) -> u32 {
+        if self.stopwatch() {
+            return 1;
+        }
+        if let Some(stations) = self.stations() {
+            return stations.len() as u32 * self.settings.laps;
+        }
+        self.round_list().map_or(self.settings.rounds, |rounds| rounds.len() as u32)
+    }
+
+    fn save_settings(&mut self) {
+        let current = self.settings_json();
+        if current != self.saved_settings {
+            let previous = std::mem::replace(&mut self.saved_settings, current);
+            // A burst of saves, like a slider being dragged, undoes in one step
+            if self.last_undo_push.is_none_or(|t| t.elapsed() >= UNDO_MERGE) {
+                self.undo.push(previous);
+                if self.undo.len() > UNDO_LIMIT {
+                    self.undo.remove(0);
+                }
+            }
+            self.last_undo_push = Some(Instant::now());
+            self.redo.clear();
+        }
+        self.write_settings();
+    }
+
+    fn write_settings(&mut self) {
+        self.settings.save_to_file(&self.settings_path);
+        self.settings_mtime = Settings::modified_time(&self.settings_path); // Our own write is not an outside change
+    }
+
+    fn settings_json(&self) -> String {
+        serde_json::to_string(&self.settings).unwrap_or_default()
+    }
+
+    fn can_undo(&self) -> bool {
+        self.state == TimerState::Idle && !self.undo.is_empty()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.state == TimerState::Idle && !self.redo.is_empty()
+    }
+
+    // Only while idle, a session runs with the settings it started with
+    fn undo_settings(&mut self) {
+        if !self.can_undo() {
+            return;
+        }
+        if let Some(previous) = self.undo.pop() {
+            let current = std::mem::replace(&mut self.saved_settings, previous);
+            self.redo.push(current);
+            self.restore_settings();
+        }
+    }
+
+    fn redo_settings(&mut self) {
+        if !self.can_redo() {
+            return;
+        }
+        if let Some(next) = self.redo.pop() {
+            let current = std::mem::replace(&mut self.saved_settings, next);
+            self.undo.push(current);
+            self.restore_settings();
+        }
+    }
+
+    // Applies saved_settings and writes it, so the file matches what's shown
+    fn restore_settings(&mut self) {
+        match serde_json::from_str(&self.saved_settings) {
+            Ok(settings) => self.settings = settings,
+            Err(err) => log::error!("Can't restore the settings snapshot: {}", err),
+        }
+        self.last_undo_push = None;
+        self.check_custom_sounds();
+        self.write_settings();
+    }
+
+    fn custom_sound_paths(&mut self) -> [(&'static str, &mut String); 3] {
+        [
+            ("Work start", &mut self.settings.rest_finish_audio),
+            ("Work end", &mut self.settings.work_finish_audio),
+            ("Complete", &mut self.settings.complete_audio),
+        ]
+    }
+
+    fn check_custom_sounds(&mut self) {
+        let errors = self
+            .custom_sound_paths()
+            .map(|(_, path)| (!path.trim().is_empty()).then(|| sounds::load_custom(path).err()).flatten());
+        self.custom_sound_errors = errors;
+    }
+
+    // The custom file when one is set and decodes, the pack's clip otherwise
+    fn cue_audio(&mut self, cue: Cue) -> std::borrow::Cow<'static, [u8]> {
+        let slot = cue.slot();
+        let path = self.custom_sound_paths()[slot].1.clone();
+        if !path.trim().is_empty() {
+            match sounds::load_custom(&path) {
+                Ok(data) => {
+                    self.custom_sound_errors[slot] = None;
+                    return data.into();
+                }
+                Err(err) => {
+                    log::warn!("Custom sound {} not used: {}", path, err);
+                    self.custom_sound_errors[slot] = Some(err);
+                }
+            }
+        }
+        sounds::pack(&self.settings.sound_pack).clip(cue)
+    }
+
+    fn play_sound(&mut self, cue: Cue) {
+        let looping = cue == Cue::Complete && self.settings.repeat_complete_until_dismissed;
+        self.play_cue(cue, looping);
+    }
+
+    fn play_cue(&mut self, cue: Cue, looping: bool) {
+        // The final round, the long break and rotating repeat their usual cue, so they can be told apart by ear
+        let times = match cue {
+            Cue::FinalRound | Cue::LongBreak => 2,
+            Cue::Rotate => 3,
+            _ => 1,
+        };
+        let voice = match cue {
+            Cue::WorkStart | Cue::FinalRound => self.round_announcement(),
+            _ => Vec::new(),
+        };
+        let part = if cue == Cue::Complete { self.settings.complete_volume } else { self.settings.cue_volume };
+        let result = self.queue_audio(voice, cue, times, looping, self.volume(part));
+        self.note_audio_result(result);
+    }
+
+    // A per-sound volume combined with the master volume, as an amplification factor
+    fn volume(&self, part: u32) -> f32 {
+        (self.settings.volume * part) as f32 / 10_000.0
+    }
+
+    // Words missing from the folder are left out, numbers past the last clip skip the announcement
+    fn round_announcement(&self) -> Vec<Vec<u8>> {
+        let dir = self.settings.voice_dir.trim();
+        if !self.settings.announce_rounds || dir.is_empty() {
+            return Vec::new();
+        }
+        let dir = Path::new(dir);
+        let Some(number) = sounds::voice_clip(dir, &(self.current_round + 1).to_string()) else {
+            return Vec::new();
+        };
+        sounds::voice_clip(dir, "round").into_iter().chain([number]).collect()
+    }
+
+    // Failures are kept so the idle screen can show them next to "Test sound"
+    fn note_audio_result(&mut self, result: Result<(), AudioError>) {
+        match result {
+            Ok(()) => self.audio_error = None,
+            Err(err @ AudioError::Busy(_)) => log::warn!("{}", err), // The output itself works
+            Err(AudioError::Unavailable(err)) => {
+                log::error!("{}", err);
+                self.audio_error = Some(err);
+            }
+        }
+    }
+
+    // The voice clips go first, so the cue follows them instead of overlapping.
+    // The phase itself has already started, audio never holds it back.
+    fn queue_audio(
+        &mut self,
+        voice: Vec<Vec<u8>>,
+        cue: Cue,
+        times: usize,
+        looping: bool,
+        volume: f32,
+    ) -> Result<(), AudioError> {
+        let mut sounds: Vec<Sound> = Vec::new();
+        for clip in voice {
+            match self.audio.decode(clip) {
+                Ok(source) => sounds.push(Box::new(source.amplify(volume))),
+                Err(err) => log::warn!("Skipping unreadable voice clip: {}", err),
+            }
+        }
+        let audio_data = self.cue_audio(cue);
+        let slot = cue.slot();
+        for _ in 0..times {
+            let source: Sound = match self.audio.decode(audio_data.to_vec()) {
+                Ok(source) => {
+                    self.cue_decode_errors[slot] = None;
+                    source
+                }
+                // Some builds of the decoders can't read every format, beeps still mark the phase
+                Err(err) => {
+                    log::warn!("Failed to decode the {:?} cue, beeping instead: {}", cue, err);
+                    self.cue_decode_errors[slot] = Some(err);
+                    Box::new(sounds::fallback_sound(cue))
+                }
+            };
+            if looping {
+                // Keep ringing with a short gap until the user dismisses it
+                let source = source.delay(COMPLETION_REPEAT_GAP).repeat_infinite();
+                sounds.push(Box::new(source.amplify(volume)));
+            } else {
+                sounds.push(Box::new(source.amplify(volume)));
+            }
+        }
+        if looping {
+            // Whatever still waits is from the session that just ended, the alarm must not be dropped
+            self.audio.clear();
+        }
+        self.audio.queue(Channel::Cues, sounds)?;
+        self.completion_alarm |= looping;
+        Ok(())
+    }
+
+    fn play_tone(&mut self, freq: f32) {
+        let volume = 0.3 * self.volume(self.settings.beep_volume);
+        let tone: Sound = Box::new(rodio::source::SineWave::new(freq).take_duration(COUNTDOWN_BEEP).amplify(volume));
+        let result = self.audio.queue(Channel::Beeps, vec![tone]);
+        self.note_audio_result(result);
+    }
+
+    // Beeps at 3, 2 and 1 seconds left, so the coming phase can be told apart without looking
+    fn countdown_beep(&mut self) {
+        let remaining = self.remaining_time;
+        if !self.settings.countdown_beeps
+            || !(1..=3).contains(&remaining)
+            || remaining >= self.phase_duration
+            || self.last_beep == Some(remaining)
+        {
+            return;
+        }
+        self.last_beep = Some(remaining);
+        let step = match self.state {
+            TimerState::Workout => remaining as i32 - 3, // Falling into rest
+            _ => 3 - remaining as i32, // Rising into work
+        };
+        self.play_tone(self.settings.countdown_base_freq * 2f32.powf(step as f32 / 6.0));
+    }
+
+    // "Switch sides" cue once per work phase, at the configured share of its length
+    fn switch_sides(&mut self) {
+        if !self.settings.switch_sides
+            || self.switch_fired
+            || self.state != TimerState::Workout
+            || self.remaining_time == 0
+        {
+            return;
+        }
+        let done = self.phase_duration - self.remaining_time.min(self.phase_duration);
+        if done * 100 < self.phase_duration * self.settings.switch_split_percent as u64 {
+            return;
+        }
+        self.switch_fired = true;
+        self.play_tone(self.settings.countdown_base_freq * 1.25);
+        if self.settings.switch_flash {
+            self.switch_flash_until = Some(Instant::now() + Duration::from_secs(1));
+        }
+    }
+
+    fn switch_flash_active(&self) -> bool {
+        self.state == TimerState::Workout && self.switch_flash_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    fn holding_phase(&self) -> bool {
+        self.settings.manual_advance && !self.advance_requested
+    }
+
+    // Time spent in the current work or rest phase past its planned end
+    fn overtime_secs(&self) -> u64 {
+        if !matches!(self.state.resumed(), TimerState::Workout | TimerState::Rest) {
+            return 0;
+        }
+        let running = self.start_time.map_or(0, |start| self.phase_elapsed(start).as_secs().saturating_sub(self.phase_duration));
+        self.phase_overtime + running
+    }
+
+    // Adds the finished phase's overtime to the session total
+    fn take_overtime(&mut self) -> u64 {
+        let overtime = self.overtime_secs();
+        self.session_overtime_secs += overtime;
+        self.phase_overtime = 0;
+        overtime
+    }
+
+    // How far the running phase's own clock is, without overtime
+    fn phase_clock_secs(&self) -> u64 {
+        match self.start_time {
+            Some(start) => self.phase_elapsed(start).as_secs().min(self.phase_duration),
+            None => self.phase_duration.saturating_sub(self.remaining_time),
+        }
+    }
+
+    // Time actually spent in the running phase, also from before a restart or an adjustment
+    fn phase_spent_secs(&self) -> u64 {
+        (self.phase_clock_secs() as i64 + self.phase_offset_secs).max(0) as u64
+    }
+
+    // The measured length of the finished phase, overtime included
+    fn take_phase_secs(&mut self) -> u64 {
+        let spent = self.phase_spent_secs() + self.take_overtime();
+        self.phase_offset_secs = 0;
+        spent
+    }
+
+    // Gentle reminder every 10 seconds while a phase waits for Next
+    fn overtime_beep(&mut self) {
+        let overtime = self.overtime_secs();
+        if !matches!(self.state, TimerState::Workout | TimerState::Rest)
+            || overtime == 0
+            || !overtime.is_multiple_of(10)
+            || self.last_overtime_beep == overtime
+        {
+            return;
+        }
+        self.last_overtime_beep = overtime;
+        self.play_tone(self.settings.countdown_base_freq * 0.75);
+    }
+
+    // Single "get ready" cue some seconds before a work or rest phase ends
+    fn pre_alert(&mut self) {
+        let threshold = self.settings.pre_alert_seconds as u64;
+        if threshold == 0
+            || self.pre_alert_fired
+            || !matches!(self.state, TimerState::Workout | TimerState::Rest)
+            || self.phase_duration <= threshold
+            || self.remaining_time > threshold
+        {
+            return;
+        }
+        self.pre_alert_fired = true;
+        self.play_tone(self.settings.countdown_base_freq * 1.5);
+    }
+
+    // Cut any cue still playing (looping completion sound included) and drop the celebration
+    fn silence(&mut self) {
+        self.audio.clear();
+        self.speech.cancel();
+        self.speech_due = None;
+        self.completion_alarm = false;
+        self.fanfare_start_time = None;
+        self.goal_reached_fanfare = false;
+    }
+
+    // Always a clean slate: no fanfare, completion sound or summary from the previous session
+    fn start(&mut self) {
+        self.silence();
+        self.pending_resume = None;
+        self.paused_remaining = None;
+        self.current_round = 0;
+        self.session_rounds = self.planned_rounds();
+        self.session_work_secs = 0;
+        self.session_rest_secs = 0;
+        self.session_pauses = 0;
+        self.session_started_at = Some(Instant::now());
+        self.paused_at = None;
+        self.session_paused_secs = 0;
+        self.session_overtime_secs = 0;
+        self.phase_overtime = 0;
+        self.phase_offset_secs = 0;
+        self.laps.clear();
+        self.last_session = None;
+        self.remember_config();
+        self.start_time = Some(Instant::now());
+        self.set_state(TimerState::LeadUp);
+        self.phase_duration = self.settings.lead_up_duration;
+        self.remaining_time = self.phase_duration;
+    }
+
+    fn set_state(&mut self, state: TimerState) {
+        log::info!(
+            "{:?} -> {:?} (round {}/{}, {}s remaining)",
+            self.state,
+            state,
+            self.current_round + 1,
+            self.session_rounds,
+            self.remaining_time
+        );
+        if state.resumed() != self.state.resumed() {
+            self.pre_alert_fired = false; // A new phase, not just a pause or resume
+            self.advance_requested = false;
+            self.last_overtime_beep = 0;
+            self.switch_fired = false;
+            self.rest_quote = None;
+            if state == TimerState::Rest && self.settings.rest_quotes {
+                self.rest_quote = self.next_quote();
+            }
+            self.speech_due = self
+                .exercise_announcement(state)
+                .filter(|_| self.settings.speak_exercises)
+                .map(|text| (Instant::now() + SPEECH_DELAY, text));
+        }
+        if state == TimerState::Idle && self.state != TimerState::Idle {
+            self.idle_since = Instant::now();
+        }
+        self.state = state;
+    }
+
+    // The exercise starting now at work, the one coming up during rest. Named rounds only.
+    fn exercise_announcement(&self, state: TimerState) -> Option<String> {
+        let (round, prefix) = match state {
+            TimerState::Workout => (self.current_round, ""),
+            TimerState::Rest if self.current_round + 1 < self.session_rounds => (self.current_round + 1, "Next: "),
+            _ => return None,
+        };
+        let name = self.round_name(round)?.trim();
+        (!name.is_empty()).then(|| format!("{}{}", prefix, name))
+    }
+
+    fn speak_due(&mut self) {
+        if self.speech_due.as_ref().is_some_and(|(at, _)| *at <= Instant::now())
+            && let Some((_, text)) = self.speech_due.take()
+        {
+            self.speech.speak(&text, self.volume(self.settings.cue_volume));
+        }
+    }
+
+    // Read again for each rest phase so edits to the file show up without a restart
+    fn next_quote(&mut self) -> Option<String> {
+        let quotes = quotes::load(&self.settings.quotes_file);
+        self.quote_index = self.quote_index.wrapping_add(1);
+        quotes.get(self.quote_index % quotes.len().max(1)).cloned()
+    }
+
+    // Every way into a paused state comes through here, so each one is counted
+    fn pause(&mut self) {
+        self.session_pauses += 1;
+        self.phase_overtime = self.overtime_secs(); // The resumed phase starts again at 0:00
+        self.set_state(self.state.paused());
+        if let Some(start) = self.start_time.take() {
+            self.paused_remaining = Some(Duration::from_secs(self.phase_duration).saturating_sub(self.phase_elapsed(start)));
+        }
+        self.paused_at = Some(Instant::now());
+        self.auto_resume_warned = false;
+    }
+
+    fn resume(&mut self) {
+        // Restored snapshots only know whole seconds
+        let remaining = self.paused_remaining.take().unwrap_or(Duration::from_secs(self.remaining_time));
+        self.start_time = Some(Instant::now() - self.real_time(Duration::from_secs(self.phase_duration).saturating_sub(remaining)));
+        if let Some(paused_at) = self.paused_at.take() {
+            self.session_paused_secs += paused_at.elapsed().as_secs();
+        }
+        self.set_state(self.state.resumed());
+    }
+
+    fn paused_secs(&self) -> Option<u64> {
+        self.paused_at.map(|paused_at| paused_at.elapsed().as_secs())
+    }
+
+    // Earlier pauses of this session plus the one still going on
+    fn total_paused_secs(&self) -> u64 {
+        self.session_paused_secs + self.paused_secs().unwrap_or(0)
+    }
+
+    // Beeps a few seconds ahead, then carries on with the paused phase
+    fn check_auto_resume(&mut self) {
+        let limit = self.settings.auto_resume_after;
+        let Some(paused) = self.paused_secs() else {
+            return;
+        };
+        if limit == 0 || !self.state.is_paused() {
+            return;
+        }
+        if paused >= limit {
+            log::info!("Auto-resuming after {}s paused", paused);
+            self.resume();
+        } else if paused + AUTO_RESUME_WARNING_SECS >= limit && !self.auto_resume_warned {
+            self.auto_resume_warned = true;
+            self.play_tone(self.settings.countdown_base_freq);
+        }
+    }
+
+    // Fine tuning while paused, at 0 the phase ends as soon as the timer resumes
+    fn adjust_remaining(&mut self, delta: i64) {
+        if !self.state.is_paused() {
+            return;
+        }
+        let spent = self.phase_spent_secs();
+        let remaining = (self.remaining_time as i64 + delta).clamp(0, MAX_ADJUSTED_SECS as i64) as u64;
+        self.remaining_time = remaining;
+        self.phase_duration = self.phase_duration.max(remaining);
+        self.paused_remaining = Some(Duration::from_secs(remaining));
+        self.phase_offset_secs = spent as i64 - self.phase_clock_secs() as i64;
+    }
+
+    fn trigger_visual_fanfare(&mut self) {
+        self.fanfare_start_time = Some(Instant::now());
+    }
+
+    // Lengthens only the rest currently running, the configured rest duration stays untouched
+    fn extend_rest(&mut self) {
+        if matches!(self.state, TimerState::Rest | TimerState::PausedRest) {
+            self.extend_phase(self.settings.rest_extension);
+        }
+    }
+
+    fn extend_phase(&mut self, secs: u64) {
+        if self.state == TimerState::Idle {
+            return;
+        }
+        self.phase_duration += secs;
+        if self.state.is_paused() {
+            self.remaining_time += secs;
+            self.paused_remaining = self.paused_remaining.map(|remaining| remaining + Duration::from_secs(secs));
+        }
+    }
+
+    // Ends the current phase early, work time only counts what was actually done
+    fn skip_phase(&mut self) {
+        self.advance_requested = true;
+        if let Some(start) = self.start_time {
+            self.phase_duration = self.phase_elapsed(start).as_secs().min(self.phase_duration);
+        } else if self.state.is_paused() {
+            self.phase_duration -= self.remaining_time.min(self.phase_duration);
+            self.remaining_time = 0;
+            self.paused_remaining = Some(Duration::ZERO); // Moves on once resumed
+        }
+    }
+
+    fn restart_phase(&mut self) {
+        if self.state == TimerState::Idle {
+            return;
+        }
+        self.phase_offset_secs = (self.phase_spent_secs() + self.overtime_secs()) as i64;
+        self.remaining_time = self.phase_duration;
+        self.pre_alert_fired = false;
+        self.switch_fired = false;
+        self.phase_overtime = 0;
+        if self.start_time.is_some() {
+            self.start_time = Some(Instant::now());
+        } else {
+            self.paused_remaining = Some(Duration::from_secs(self.phase_duration));
+        }
+    }
+
+    // One-off extra round for the running session, never written back to the settings
+    fn add_round(&mut self) {
+        if matches!(self.state, TimerState::Workout | TimerState::Rest) && !self.stopwatch() {
+            self.session_rounds += 1;
+        }
+    }
+
+    // Uses the session target, so rounds added on the fly move the final round too
+    fn is_final_round(&self) -> bool {
+        self.state != TimerState::Idle && self.current_round + 1 == self.session_rounds
+    }
+
+    fn rounds_target(&self) -> u32 {
+        match self.state {
+            TimerState::Idle => self.planned_rounds(),
+            _ => self.session_rounds,
+        }
+    }
+
+    // Messages sent by later launches of the app
+    fn handle_instance_messages(&mut self, ctx: &egui::Context) {
+        let Some(instance) = &self.instance else {
+            return;
+        };
+        for message in instance.poll() {
+            let link = message.strip_prefix("open:").map(str::to_string);
+            if message == "focus" || link.is_some() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
+            if let Some(link) = link {
+                self.open_link(&link);
+            }
+        }
+    }
+
+    fn handle_keyboard(&mut self, ctx: &egui::Context) {
+        // The focus ring only shows while moving around with Tab, a click hides it again
+        if ctx.input(|i| i.key_pressed(egui::Key::Tab)) {
+            self.keyboard_navigation = true;
+        } else if ctx.input(|i| i.pointer.any_pressed()) {
+            self.keyboard_navigation = false;
+        }
+        if ctx.wants_keyboard_input() || self.confirming_stop || self.confirming_start || self.capturing_key.is_some()
+            || self.palette_query.is_some()
+        {
+            return; // Typing into a text field, a prompt or the palette owns the keys, or a key is being remapped
+        }
+        let focused = ctx.memory(|m| m.focused().is_some());
+        for shortcut in SHORTCUTS {
+            if self.shortcut_active(shortcut.scope, focused) && ctx.input(|i| shortcut.pressed(i, &self.settings.key_bindings)) {
+                self.run_action(ctx, shortcut.action);
+            }
+        }
+    }
+
+    fn shortcut_active(&self, scope: Scope, focused: bool) -> bool {
+        match scope {
+            Scope::Always => true,
+            Scope::Leader => !self.following(), // The leader starts and stops the session
+            // With a control focused, Space activates it and the arrows move a slider
+            Scope::Unfocused => !self.following() && !focused,
+        }
+    }
+
+    fn run_action(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::Palette => {
+                self.palette_query = Some(String::new());
+                self.palette_selected = 0;
+            }
+            Action::ToggleHelp => self.show_keyboard_help = !self.show_keyboard_help,
+            Action::Fullscreen => {
+                let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+                ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!fullscreen));
+            }
+            Action::Escape if self.show_keyboard_help => self.show_keyboard_help = false,
+            Action::Escape => ctx.memory_mut(|m| {
+                if let Some(id) = m.focused() {
+                    m.surrender_focus(id);
+                }
+            }),
+            Action::StartPause => self.start_or_pause(),
+            Action::Stop | Action::Skip if self.state == TimerState::Idle => {}
+            Action::Stop => self.request_stop(),
+            Action::Skip => self.skip_phase(),
+            Action::RestartPhase => self.restart_phase(),
+            Action::Extend => self.extend_rest(),
+            Action::AddRound => self.add_round(),
+            Action::Lap => self.lap(),
+            Action::Adjust(delta) => self.adjust_remaining(delta),
+            Action::Undo => self.undo_settings(),
+            Action::Redo => self.redo_settings(),
+            Action::Preview(speed) => self.set_preview_speed(speed),
+            Action::BackupHistory => {
+                self.backup_history();
+                if let Some(message) = self.backup_message.clone() {
+                    self.show_notice(message); // The History section may well be collapsed
+                }
+            }
+        }
+    }
+
+    fn command_name(&self, action: Action) -> String {
+        match action {
+            Action::StartPause if self.state == TimerState::Idle => "Start".to_string(),
+            Action::StartPause if self.state.is_paused() => "Resume".to_string(),
+            Action::StartPause => "Pause".to_string(),
+            Action::Extend => format!("+{}s", self.settings.rest_extension),
+            Action::Adjust(delta) if delta > 0 => format!("Add {} seconds", delta),
+            Action::Adjust(delta) => format!("Take off {} seconds", -delta),
+            Action::Preview(1) => "Back to real time".to_string(),
+            Action::Preview(speed) => format!("Preview at {}× speed, for testing cues", speed),
+            action => action.name().to_string(),
+        }
+    }
+
+    // Why an action can't run right now, shown next to it grayed out
+    fn command_unavailable(&self, action: Action) -> Option<&'static str> {
+        let idle = self.state == TimerState::Idle;
+        match action {
+            Action::StartPause | Action::Stop | Action::Skip | Action::RestartPhase | Action::Extend | Action::AddRound
+            | Action::Lap | Action::Adjust(_)
+                if self.following() =>
+            {
+                Some("Following another timer")
+            }
+            Action::Stop | Action::Skip | Action::RestartPhase | Action::Extend if idle => Some("No session running"),
+            Action::Extend if self.state.resumed() != TimerState::Rest => Some("Only during rest"),
+            Action::AddRound if self.stopwatch() || !matches!(self.state, TimerState::Workout | TimerState::Rest) => {
+                Some("Only during work or rest of a timed session")
+            }
+            Action::Lap if !self.stopwatch() || self.state.resumed() != TimerState::Workout => {
+                Some("Only while the stopwatch runs")
+            }
+            Action::Adjust(_) if !self.state.is_paused() => Some("Only while paused"),
+            Action::Undo | Action::Redo if !idle => Some("Only while idle"),
+            Action::Undo if !self.can_undo() => Some("Nothing to undo"),
+            Action::Redo if !self.can_redo() => Some("Nothing to redo"),
+            Action::BackupHistory if self.settings.history_backup_file.trim().is_empty() => {
+                Some("No backup file set under History")
+            }
+            Action::Preview(1) if !self.previewing() => Some("Already running in real time"),
+            Action::Preview(speed) if speed == self.preview_speed => Some("Already at this speed"),
+            _ => None,
+        }
+    }
+
+    fn backup_history(&mut self) {
+        let path = self.settings.history_backup_file.trim().to_string();
+        if path.is_empty() {
+            return;
+        }
+        self.backup_message = Some(match self.history.backup(Path::new(&path)) {
+            Ok(()) => format!("Saved {}", format::count(self.history.records.len() as u64, "session")),
+            Err(err) => err,
+        });
+    }
+
+    fn start_or_pause(&mut self) {
+        match self.state {
+            TimerState::Idle => self.request_start(),
+            state if state.is_paused() => self.resume(),
+            _ => self.pause(),
+        }
+    }
+
+    fn handle_gamepad(&mut self, ctx: &egui::Context) {
+        let wanted = self.settings.gamepad && GamepadInput::available();
+        if wanted != self.gamepad.is_some() {
+            self.gamepad = wanted.then(|| GamepadInput::start(ctx.clone()));
+        }
+        let Some(gamepad) = &self.gamepad else {
+            return;
+        };
+        for command in gamepad.poll() {
+            if self.following() {
+                continue; // The leader starts and stops the session
+            }
+            // While a prompt is up, A goes ahead (ends or starts the session) and B backs out
+            if self.confirming_stop {
+                match command {
+                    PadCommand::StartPause => {
+                        self.confirming_stop = false;
+                        self.stop();
+                    }
+                    PadCommand::Stop => self.confirming_stop = false,
+                    _ => {}
+                }
+                continue;
+            }
+            if self.confirming_start {
+                match command {
+                    PadCommand::StartPause => {
+                        self.confirming_start = false;
+                        self.start();
+                    }
+                    PadCommand::Stop => self.confirming_start = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match command {
+                PadCommand::StartPause => self.start_or_pause(),
+                PadCommand::Stop if self.state != TimerState::Idle => self.request_stop(),
+                PadCommand::Skip if self.state != TimerState::Idle => self.skip_phase(),
+                PadCommand::Adjust(delta) => self.adjust_remaining(delta),
+                _ => {}
+            }
+        }
+    }
+
+    // Live status line for streaming overlays, empty while idle so the overlay clears
+    fn overlay_text(&self) -> String {
+        if self.state == TimerState::Idle {
+            return String::new();
+        }
+        let remaining = format::clock(self.remaining_time);
+        if self.settings.overlay_json {
+            serde_json::json!({
+                "phase": self.state.phase_key(),
+                "paused": self.state.is_paused(),
+                "round": self.current_round + 1,
+                "rounds": self.session_rounds,
+                "remaining": remaining,
+            })
+            .to_string()
+        } else {
+            format!("{} {} | {}", self.status_name(), remaining, self.round_label(self.session_rounds))
+        }
+    }
+
+    fn round_label(&self, total: u32) -> String {
+        let total = if self.stopwatch() { 0 } else { total };
+        format::round_progress(self.current_round + 1, total, self.settings.count_rounds_down)
+    }
+
+    fn animations_enabled(&self) -> bool {
+        !self.settings.reduced_motion && !self.settings.battery_saver
+    }
+
+    // 0..=1 brightness of the pulse that calls attention back during the last seconds of rest
+    fn rest_pulse(&self) -> Option<f32> {
+        if self.state != TimerState::Rest || self.remaining_time > REST_PULSE_SECS || !self.animations_enabled() {
+            return None;
+        }
+        let elapsed = self.start_time?.elapsed().as_secs_f32();
+        Some(0.5 - 0.5 * (elapsed * std::f32::consts::TAU).cos())
+    }
+
+    // Exact remaining time of the phase, also while it's running between whole seconds
+    fn remaining_ms(&self) -> u64 {
+        match self.start_time {
+            Some(start) => (self.phase_duration * 1000).saturating_sub(self.phase_elapsed(start).as_millis() as u64),
+            None => self.remaining_time * 1000,
+        }
+    }
+
+    fn status_line(&self) -> String {
+        serde_json::json!({
+            "state": self.state.phase_key(),
+            "paused": self.state.is_paused(),
+            "round": self.current_round + 1,
+            "rounds": self.rounds_target(),
+            "remaining_ms": self.remaining_ms(),
+        })
+        .to_string()
+    }
+
+    fn update_mqtt(&mut self, ctx: &egui::Context) {
+        // Reconnects once the typing is done, not on every keystroke
+        if !ctx.wants_keyboard_input() {
+            self.mqtt_config = self.settings.mqtt_enabled.then(|| MqttConfig {
+                broker: self.settings.mqtt_broker.trim().to_string(),
+                username: self.settings.mqtt_username.clone(),
+                password: self.settings.mqtt_password.clone(),
+                topic_prefix: self.settings.mqtt_topic.trim().to_string(),
+            });
+        }
+        let round = self.current_round + 1;
+        let payload = serde_json::json!({
+            "phase": self.state.phase_key(),
+            "paused": self.state.is_paused(),
+            "round": round,
+            "remaining": self.remaining_time,
+        })
+        .to_string();
+        self.mqtt.update(self.mqtt_config.clone(), format!("{:?} {}", self.state, round), payload);
+    }
+
+    // Small dot next to the heading while publishing, the details are in its tooltip
+    fn mqtt_indicator_ui(&self, ui: &mut egui::Ui) {
+        let Some(state) = self.mqtt.state() else {
+            return;
+        };
+        let (color, text) = match state {
+            LinkState::Connecting => (ui.visuals().warn_fg_color, "MQTT: connecting".to_string()),
+            LinkState::Connected => (GOAL_COLOR, format!("MQTT: publishing to {}", self.settings.mqtt_broker)),
+            LinkState::Failed(err) => (ui.visuals().error_fg_color, format!("MQTT: {}", err)),
+        };
+        ui.label(egui::RichText::new("●").small().color(color)).on_hover_text(text);
+    }
+
+    fn presence_status(&self) -> Option<PresenceStatus> {
+        if self.state == TimerState::Idle {
+            return None;
+        }
+        Some(PresenceStatus {
+            details: format!("{} — {}", self.status_name(), self.round_label(self.session_rounds)),
+            state: format!("{} left", format::clock(self.remaining_time)),
+        })
+    }
+
+    // Progress towards the weekly goal as (done, goal)
+    fn weekly_goal_progress(&self) -> Option<(u32, u32)> {
+        if self.settings.weekly_goal == 0 {
+            return None;
+        }
+        let (sessions, work_secs) = self
+            .history
+            .week_totals(chrono::Local::now().date_naive(), self.settings.week_start, self.pomodoro());
+        let done = match self.settings.weekly_goal_kind {
+            GoalKind::Sessions => sessions,
+            GoalKind::WorkMinutes => (work_secs / 60) as u32,
+        };
+        Some((done, self.settings.weekly_goal))
+    }
+
+    fn record_session(&mut self) {
+        let goal_met_before = self.weekly_goal_progress().is_some_and(|(done, goal)| done >= goal);
+        let record = SessionRecord {
+            finished_at: chrono::Local::now().timestamp(),
+            rounds: self.session_rounds,
+            workout_duration: self.settings.workout_duration,
+            rest_duration: self.settings.rest_duration,
+            work_secs: self.session_work_secs,
+            tag: self.settings.tag.trim().to_string(),
+            total_secs: self.session_started_at.map_or(0, |t| t.elapsed().as_secs()),
+            pauses: self.session_pauses,
+            kcal: self.session_kcal(),
+            paused_secs: self.session_paused_secs,
+            planned_secs: self.settings.lead_up_duration + self.rounds_secs(0, self.session_rounds),
+            pomodoro: self.pomodoro(),
+            overtime_secs: self.session_overtime_secs,
+            lap_secs: self.laps.iter().map(Duration::as_secs_f64).collect(),
+            rest_secs: self.session_rest_secs,
+            planned_work_secs: if self.stopwatch() { 0 } else { self.planned_phase_secs().0 },
+            planned_rest_secs: if self.stopwatch() { 0 } else { self.planned_phase_secs().1 },
+        };
+        if self.previewing() {
+            self.last_session = Some(record); // Shown in the summary, kept out of the history and the stats
+            return;
+        }
+        self.history.add(record.clone(), &self.history_path);
+        self.last_session = Some(record);
+        let goal_met_now = self.weekly_goal_progress().is_some_and(|(done, goal)| done >= goal);
+        self.goal_reached_fanfare = goal_met_now && !goal_met_before;
+    }
+
+    fn stop(&mut self) {
+        self.silence();
+        self.set_state(TimerState::Idle);
+        self.start_time = None;
+        self.paused_remaining = None;
+        self.paused_at = None;
+        self.session_paused_secs = 0;
+        self.laps.clear();
+        self.remaining_time = 0;
+        self.current_round = 0;
+        SessionSnapshot::delete();
+    }
+
+    // Over the limit for the session length, the total from the summary under Start
+    fn over_length_limit(&self) -> bool {
+        let limit = self.settings.session_length_warning_minutes;
+        limit > 0 && !self.stopwatch() && self.session_total_secs() > limit * 60
+    }
+
+    fn request_start(&mut self) {
+        if self.over_length_limit() {
+            self.confirming_start = true;
+        } else {
+            self.start();
+        }
+    }
+
+    fn confirm_start_ui(&mut self, ctx: &egui::Context) {
+        if !self.confirming_start {
+            return;
+        }
+        let mut confirm = false;
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("confirm_start")).show(ctx, |ui| {
+            ui.label(format!(
+                "This session takes {}, longer than your limit of {}",
+                format::clock(self.session_total_secs()),
+                format::count(self.settings.session_length_warning_minutes, "minute")
+            ));
+            ui.horizontal(|ui| {
+                confirm = ui.button("Start anyway").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        // Escape or a click next to the prompt cancels
+        if confirm {
+            self.confirming_start = false;
+            self.start();
+        } else if cancel || modal.should_close() {
+            self.confirming_start = false;
+        }
+    }
+
+    // Stop goes through here from every button and menu, so the prompt can't be bypassed
+    fn request_stop(&mut self) {
+        let in_session = matches!(self.state.resumed(), TimerState::Workout | TimerState::Rest);
+        if self.settings.confirm_stop && in_session && self.current_round > 0 {
+            self.confirming_stop = true;
+        } else {
+            self.stop();
+        }
+    }
+
+    fn confirm_stop_ui(&mut self, ctx: &egui::Context) {
+        if !self.confirming_stop {
+            self.stop_prompt_shown = false;
+            return;
+        }
+        let left = self.session_rounds.saturating_sub(self.current_round + 1);
+        let mut confirm = self.stop_prompt_shown && ctx.input(|i| i.key_pressed(egui::Key::Enter));
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("confirm_stop")).show(ctx, |ui| {
+            ui.label(format!("End session? {} remaining", format::count(left as u64, "round")));
+            ui.horizontal(|ui| {
+                confirm |= ui.button("End session").clicked();
+                cancel = ui.button("Keep going").clicked();
+            });
+        });
+        self.stop_prompt_shown = true;
+        // Escape or a click next to the prompt cancels
+        if confirm {
+            self.confirming_stop = false;
+            self.stop();
+        } else if cancel || modal.should_close() {
+            self.confirming_stop = false;
+        }
+    }
+
+    fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            state: self.state,
+            current_round: self.current_round,
+            rounds: self.session_rounds,
+            phase_duration: self.phase_duration,
+            remaining_time: self.remaining_time,
+            saved_at: unix_now(),
+            elapsed_secs: self.session_started_at.map_or(0, |t| t.elapsed().as_secs()),
+            work_secs: self.session_work_secs,
+            rest_secs: self.session_rest_secs,
+            pauses: self.session_pauses,
+            paused_secs: self.total_paused_secs(),
+            phase_offset_secs: self.phase_offset_secs,
+        }
+    }
+
+    fn persist_snapshot(&mut self) {
+        if self.state == TimerState::Idle || self.previewing() {
+            return; // A preview is never offered for resuming after a restart
+        }
+        if self.last_snapshot.is_none_or(|saved| saved.elapsed() >= SNAPSHOT_INTERVAL) {
+            self.snapshot().save_to_file();
+            self.last_snapshot = Some(Instant::now());
+            log::debug!("Saved session snapshot");
+        }
+    }
+
+    fn restore_snapshot(&mut self, snapshot: SessionSnapshot) {
+        self.silence();
+        self.last_session = None;
+        // Always come back paused so the user decides when to continue
+        self.set_state(snapshot.state.paused());
+        self.current_round = snapshot.current_round;
+        self.session_rounds = snapshot.rounds;
+        self.phase_duration = snapshot.phase_duration;
+        self.remaining_time = snapshot.remaining_time.min(snapshot.phase_duration);
+        self.start_time = None;
+        self.paused_remaining = None;
+        self.paused_at = Some(Instant::now());
+        self.auto_resume_warned = false;
+        self.session_started_at = Some(Instant::now() - Duration::from_secs(snapshot.elapsed_secs));
+        self.session_work_secs = snapshot.work_secs;
+        self.session_rest_secs = snapshot.rest_secs;
+        self.session_pauses = snapshot.pauses;
+        self.session_paused_secs = snapshot.paused_secs;
+        self.phase_offset_secs = snapshot.phase_offset_secs;
+    }
+
+    // Time into the running phase. Everything the session is timed by goes through here and
+    // real_time, which is all preview mode needs to run it faster.
+    fn phase_elapsed(&self, start: Instant) -> Duration {
+        start.elapsed() * self.preview_speed
+    }
+
+    // How long a stretch of session time really takes
+    fn real_time(&self, session: Duration) -> Duration {
+        session / self.preview_speed
+    }
+
+    // The running phase carries on from where it is, only faster or slower from here
+    fn set_preview_speed(&mut self, speed: u32) {
+        let elapsed = self.start_time.map(|start| self.phase_elapsed(start));
+        self.preview_speed = speed.max(1);
+        if let Some(elapsed) = elapsed {
+            self.start_time = Some(Instant::now() - self.real_time(elapsed));
+        }
+        log::info!("Preview speed {}×", self.preview_speed);
+    }
+
+    fn previewing(&self) -> bool {
+        self.preview_speed > 1
+    }
+
+    // Start the next phase where the previous one actually ended so late frames don't add drift
+    fn next_phase_start(&self, start: Instant) -> Instant {
+        let end = start + self.real_time(Duration::from_secs(self.phase_duration));
+        if end.elapsed() > Duration::from_secs(1) {
+            Instant::now() // Far behind (e.g. after sleep), don't replay missed phases
+        } else {
+            end
+        }
+    }
+
+    // Time until the displayed second changes, so slow repaints still tick on time
+    fn time_to_next_tick(&self) -> Duration {
+        match self.start_time {
+            Some(start) => self.real_time(Duration::from_millis(1000 - (self.phase_elapsed(start).as_millis() % 1000) as u64)),
+            None => Duration::from_secs(1),
+        }
+    }
+
+    // Commands only ever run when the user enabled hooks and configured one for this event
+    fn run_hook(&self, command: &str, phase: &str, round: u32) {
+        if self.settings.hooks_enabled {
+            self.hooks.run(command, phase, round);
+        }
+    }
+
+    fn complete_session(&mut self) {
+        self.set_state(TimerState::Idle);
+        self.start_time = None;
+        self.current_round = 0;
+        SessionSnapshot::delete(); // Finished cleanly, nothing to resume
+        self.record_session();
+        self.play_sound(Cue::Complete);
+        self.trigger_visual_fanfare();
+        self.run_hook(&self.settings.complete_command, "complete", self.session_rounds);
+    }
+
+    fn update(&mut self) {
+        self.check_auto_resume();
+        if let Some(start) = self.start_time {
+            let elapsed = self.phase_elapsed(start).as_secs();
+
+            match self.state {
+                TimerState::LeadUp => {
+                    // Handle lead-up phase
+                    self.remaining_time = self.phase_duration.saturating_sub(elapsed);
+                    if elapsed >= self.phase_duration {
+                        self.phase_offset_secs = 0; // The lead-up isn't counted anywhere
+                        self.set_state(TimerState::Workout);
+                        self.start_time = Some(self.next_phase_start(start));
+                        self.phase_duration = self.round_durations(self.current_round).0;
+                        self.remaining_time = self.phase_duration;
+                        self.play_sound(if self.is_final_round() { Cue::FinalRound } else { Cue::WorkStart });
+                        self.run_hook(&self.settings.work_start_command, "work", self.current_round + 1);
+                    }
+                }
+                TimerState::Workout => {
+                    self.remaining_time = self.phase_duration.saturating_sub(elapsed);
+                    let ended = elapsed >= self.phase_duration && !self.holding_phase();
+                    if ended && self.session_rounds == 1 {
+                        // Nothing follows a single round, so skip the rest and finish right away
+                        self.session_work_secs += self.take_phase_secs();
+                        self.complete_session();
+                    } else if ended {
+                        self.session_work_secs += self.take_phase_secs();
+                        self.set_state(TimerState::Rest);
+                        self.start_time = Some(self.next_phase_start(start));
+                        self.phase_duration = self.rest_duration(self.current_round);
+                        self.remaining_time = self.phase_duration;
+                        let cue = if self.is_long_break(self.current_round) {
+                            Cue::LongBreak
+                        } else if self.is_rotation(self.current_round) {
+                            Cue::Rotate
+                        } else {
+                            Cue::WorkEnd
+                        };
+                        self.play_sound(cue);
+                        self.run_hook(&self.settings.rest_start_command, "rest", self.current_round + 1);
+                    }
+                }
+                TimerState::Rest => {
+                    self.remaining_time = self.phase_duration.saturating_sub(elapsed);
+                    if elapsed >= self.phase_duration && !self.holding_phase() {
+                        self.session_rest_secs += self.take_phase_secs();
+                        if self.current_round + 1 < self.session_rounds {
+                            self.current_round += 1;
+                            self.set_state(TimerState::Workout);
+                            self.start_time = Some(self.next_phase_start(start));
+                            self.phase_duration = self.round_durations(self.current_round).0;
+                            self.remaining_time = self.phase_duration;
+                            self.play_sound(if self.is_final_round() { Cue::FinalRound } else { Cue::WorkStart });
+                            self.run_hook(&self.settings.work_start_command, "work", self.current_round + 1);
+                        } else {
+                            self.complete_session();
+                        }
+                    }
+                }
+                TimerState::PausedLeadUp | TimerState::PausedWorkout | TimerState::PausedRest => {
+                    // Do nothing while paused
+                }
+                TimerState::Idle => {
+                    // Do nothing while idle
+                }
+            }
+            self.countdown_beep();
+            self.pre_alert();
+            self.switch_sides();
+            self.overtime_beep();
+        }
+    }
+}
+
+impl WorkoutTimer {
+    // Right-click menu on the timer area, the same actions as the buttons
+    fn context_menu_ui(&mut self, ui: &mut egui::Ui) {
+        for action in [Action::StartPause, Action::Skip, Action::RestartPhase, Action::Extend, Action::Stop] {
+            let unavailable = self.command_unavailable(action);
+            let button = ui
+                .add_enabled(unavailable.is_none(), egui::Button::new(self.command_name(action)))
+                .on_disabled_hover_text(unavailable.unwrap_or_default());
+            if button.clicked() {
+                self.run_action(ui.ctx(), action);
+                ui.close_menu();
+            }
+        }
+    }
+
+    // Ctrl+K: every action in one searchable list, picked with the arrows and Enter
+    fn command_palette_ui(&mut self, ctx: &egui::Context) {
+        let Some(mut query) = self.palette_query.take() else {
+            return;
+        };
+        let mut matches: Vec<(i32, Action)> = shortcuts::COMMANDS
+            .iter()
+            .filter_map(|&action| shortcuts::fuzzy_score(&query, &self.command_name(action)).map(|score| (score, action)))
+            .collect();
+        matches.sort_by_key(|(score, _)| -score); // Stable, so ties keep the list order
+        let (up, down, enter) = ctx.input_mut(|i| {
+            (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+            )
+        });
+        if up {
+            self.palette_selected = self.palette_selected.saturating_sub(1);
+        }
+        if down {
+            self.palette_selected += 1;
+        }
+        self.palette_selected = self.palette_selected.min(matches.len().saturating_sub(1));
+
+        let mut picked = None;
+        let modal = egui::Modal::new(egui::Id::new("command_palette")).show(ctx, |ui| {
+            ui.set_width(360.0);
+            let search = ui.add(egui::TextEdit::singleline(&mut query).hint_text("Search actions").desired_width(f32::INFINITY));
+            search.request_focus();
+            if search.changed() {
+                self.palette_selected = 0;
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                if matches.is_empty() {
+                    ui.weak("No matching action");
+                }
+                for (index, &(_, action)) in matches.iter().enumerate() {
+                    let unavailable = self.command_unavailable(action);
+                    let selected = index == self.palette_selected;
+                    ui.horizontal(|ui| {
+                        let row = ui
+                            .add_enabled(unavailable.is_none(), egui::SelectableLabel::new(selected, self.command_name(action)))
+                            .on_disabled_hover_text(unavailable.unwrap_or_default());
+                        if selected && (up || down) {
+                            row.scroll_to_me(None);
+                        }
+                        if row.clicked() || (selected && enter && unavailable.is_none()) {
+                            picked = Some(action);
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| match unavailable {
+                            Some(reason) => ui.weak(reason),
+                            None => ui.weak(shortcuts::label_of(action, &self.settings.key_bindings)),
+                        });
+                    });
+                }
+            });
+        });
+        if let Some(action) = picked {
+            self.run_action(ctx, action);
+        } else if !modal.should_close() {
+            self.palette_query = Some(query);
+        }
+    }
+
+    // The whole session as one bar, with a tick where each later round starts
+    fn session_progress_ui(&self, ui: &mut egui::Ui) {
+        const HEIGHT: f32 = 8.0;
+        const MIN_TICK_GAP: f32 = 4.0; // Closer ticks would blur into a solid bar
+        let (done, ticks) = self.session_progress();
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), HEIGHT), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let mut filled = rect;
+        filled.set_width(rect.width() * done.clamp(0.0, 1.0));
+        painter.rect_filled(filled, 2.0, self.phase_color());
+
+        if rect.width() / (ticks.len() + 1) as f32 >= MIN_TICK_GAP {
+            let stroke = egui::Stroke::new(1.0, ui.visuals().text_color());
+            for tick in ticks {
+                let x = rect.left() + rect.width() * tick;
+                painter.vline(x, rect.y_range(), stroke);
+            }
+        }
+    }
+
+    // One dot per round: filled when done, pulsing for the current one, hollow for the rest
+    fn round_dots_ui(&self, ui: &mut egui::Ui) {
+        const RADIUS: f32 = 5.0;
+        const GAP: f32 = 4.0;
+        let total = self.rounds_target() as usize;
+        let pitch = 2.0 * RADIUS + GAP;
+        let width = ui.available_width();
+        let per_row = (((width + GAP) / pitch).floor() as usize).max(1); // Wraps onto more rows
+        let rows = total.div_ceil(per_row);
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, rows as f32 * pitch), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+
+        let color = match self.state {
+            TimerState::Idle => ui.visuals().weak_text_color(),
+            _ => self.phase_color(),
+        };
+        let pulse = if self.animations_enabled() {
+            0.6 + 0.4 * (ui.input(|i| i.time) as f32 * 4.0).sin()
+        } else {
+            1.0
+        };
+        for i in 0..total {
+            let center = rect.min + egui::vec2(
+                RADIUS + (i % per_row) as f32 * pitch,
+                RADIUS + (i / per_row) as f32 * pitch,
+            );
+            let round = i as u32;
+            if self.state != TimerState::Idle && round < self.current_round {
+                painter.circle_filled(center, RADIUS, color);
+            } else if self.state != TimerState::Idle && round == self.current_round {
+                painter.circle_filled(center, RADIUS, color.gamma_multiply(pulse));
+            } else {
+                painter.circle_stroke(center, RADIUS - 0.5, egui::Stroke::new(1.0, color));
+            }
+        }
+    }
+
+    // Work done so far, including the part of the work phase in progress
+    fn live_work_secs(&self) -> u64 {
+        match self.state {
+            TimerState::Workout | TimerState::PausedWorkout => {
+                self.session_work_secs + self.phase_spent_secs()
+            }
+            _ => self.session_work_secs,
+        }
+    }
+
+    fn session_kcal(&self) -> Option<f32> {
+        estimate_kcal(self.settings.intensity_met, self.settings.body_weight_kg, self.live_work_secs())
+    }
+
+    fn estimates_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Body weight");
+            changed |= ui.add(egui::DragValue::new(&mut self.settings.body_weight_kg).range(0.0..=300.0).suffix(" kg"))
+                .on_hover_text("0 turns the calorie estimate off")
+                .changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Intensity");
+            for (name, met) in MET_PRESETS {
+                changed |= ui.radio_value(&mut self.settings.intensity_met, met, name).changed();
+            }
+            changed |= ui.add(egui::DragValue::new(&mut self.settings.intensity_met).range(1.0..=20.0).speed(0.1).suffix(" MET")).changed();
+        });
+        if changed {
+            self.save_settings();
+        }
+    }
+
+    // Large "GET READY" with the seconds left, so the lead-up can't be mistaken for work
+    fn lead_up_ui(&self, ui: &mut egui::Ui) {
+        let color = self.phase_color();
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("GET READY").size(40.0).strong().color(color));
+            // remaining_time only changes on whole seconds, so the number never flickers
+            ui.label(
+                egui::RichText::new(self.remaining_time.to_string())
+                    .font(self.countdown_font(96.0))
+                    .strong()
+                    .color(color),
+            );
+            if self.state.is_paused() {
+                ui.label(
+                    egui::RichText::new(" PAUSED ")
+                        .strong()
+                        .color(egui::Color32::WHITE)
+                        .background_color(egui::Color32::from_gray(0x60)),
+                );
+            }
+        });
+    }
+
+    // Visible outline around the focused control while navigating with the keyboard
+    fn paint_focus_ring(&self, ctx: &egui::Context) {
+        if !self.keyboard_navigation {
+            return;
+        }
+        let Some(response) = ctx.memory(|m| m.focused()).and_then(|id| ctx.read_response(id)) else {
+            return;
+        };
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("focus_ring")));
+        let stroke = egui::Stroke::new(2.0, ctx.style().visuals.selection.stroke.color);
+        painter.rect_stroke(response.rect.expand(2.0), 4.0, stroke, egui::StrokeKind::Outside);
+    }
+
+    fn following(&self) -> bool {
+        self.settings.sync_mode == SyncMode::Follower
+    }
+
+    fn sync_packet(&self) -> SyncPacket {
+        let color = self.phase_color();
+        SyncPacket {
+            state: self.state,
+            phase: self.phase_name().to_string(),
+            color: [color.r(), color.g(), color.b()],
+            round: self.current_round + 1,
+            rounds: self.rounds_target(),
+            phase_duration: self.phase_duration,
+            remaining_ms: self.remaining_ms(),
+            sent_at_ms: sync::unix_ms(),
+        }
+    }
+
+    fn update_sync(&mut self) {
+        let config = (self.settings.sync_mode, self.settings.sync_port);
+        if self.sync_config != Some(config) {
+            self.sync_config = Some(config);
+            self.sync_follower = None;
+            self.sync_error = None;
+            if config.0 == SyncMode::Follower {
+                match SyncFollower::start(config.1) {
+                    Ok(follower) => self.sync_follower = Some(follower),
+                    Err(err) => {
+                        log::warn!("Can't follow on port {}: {}", config.1, err);
+                        self.sync_error = Some(format!("Can't listen on port {}: {}", config.1, err));
+                    }
+                }
+            }
+        }
+        match self.settings.sync_mode {
+            SyncMode::Leader if self.sync_leader.due() => {
+                let packet = self.sync_packet();
+                self.sync_leader.send(self.settings.sync_port, packet);
+            }
+            SyncMode::Follower => {
+                if let Some(follower) = &mut self.sync_follower {
+                    follower.poll();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Replaces the controls while following, only the leader runs the session
+    fn follower_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(err) = &self.sync_error {
+            ui.colored_label(ui.visuals().warn_fg_color, err);
+        }
+        if let Some(follower) = &self.sync_follower {
+            let connected = follower.connected();
+            match follower.latest() {
+                Some((_, from)) if connected => ui.label(format!("Following the timer at {}", from.ip())),
+                Some(_) => ui.colored_label(ui.visuals().warn_fg_color, "Connection to the leader lost"),
+                None => ui.label(format!("Waiting for a leader on port {}", self.settings.sync_port)),
+            };
+            if let Some((packet, _)) = follower.latest() {
+                let color = if connected {
+                    egui::Color32::from_rgb(packet.color[0], packet.color[1], packet.color[2])
+                } else {
+                    ui.visuals().weak_text_color()
+                };
+                let remaining = packet.remaining_ms_now().div_ceil(1000);
+                ui.vertical_centered(|ui| {
+                    let paused = if packet.state.is_paused() { "Paused " } else { "" };
+                    ui.label(egui::RichText::new(format!("{}{}", paused, packet.phase)).size(32.0).strong().color(color));
+                    ui.label(egui::RichText::new(format::clock(remaining)).font(self.countdown_font(72.0)).color(color));
+                    if packet.state != TimerState::Idle {
+                        ui.label(format::round_progress(packet.round, packet.rounds, self.settings.count_rounds_down));
+                    }
+                });
+                ui.add(egui::ProgressBar::new(phase_progress(remaining, packet.phase_duration)).fill(color));
+            }
+        }
+        if ui.button("Stop following").clicked() {
+            self.settings.sync_mode = SyncMode::Off;
+            self.save_settings();
+        }
+    }
+
+    fn update_banner_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some(release) = self.update_checker.poll() {
+            self.available_update = Some(release);
+        }
+        let Some(release) = &self.available_update else {
+            return;
+        };
+        let mut dismiss = false;
+        ui.group(|ui| {
+            ui.label(format!("Version {} is available", release.version));
+            ui.horizontal(|ui| {
+                if ui.button("Open release page").clicked() {
+                    ui.ctx().open_url(egui::OpenUrl::new_tab(&release.url));
+                }
+                dismiss = ui.button("Dismiss").clicked();
+            });
+        });
+        if dismiss {
+            self.available_update = None;
+        }
+    }
+
+    fn crash_notice_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(path) = &self.crash_report else {
+            return;
+        };
+        let mut dismiss = false;
+        ui.group(|ui| {
+            ui.colored_label(ui.visuals().warn_fg_color, "The timer closed unexpectedly last time");
+            ui.label(format!("A crash report was saved to {}", path.display()));
+            dismiss = ui.button("Dismiss").clicked();
+        });
+        if dismiss {
+            self.crash_report = None;
+        }
+    }
+
+    // A startup notice about an unreadable file, shown until dismissed
+    fn file_notice_ui(ui: &mut egui::Ui, notice: &mut Option<String>) {
+        let Some(text) = notice else {
+            return;
+        };
+        let mut dismiss = false;
+        ui.group(|ui| {
+            ui.colored_label(ui.visuals().warn_fg_color, text.as_str());
+            dismiss = ui.button("Dismiss").clicked();
+        });
+        if dismiss {
+            *notice = None;
+        }
+    }
+
+    fn keyboard_help_ui(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_keyboard_help;
+        let frame = egui::Frame::window(&ctx.style()).multiply_with_opacity(0.9);
+        egui::Window::new("Keyboard")
+            .open(&mut open)
+            .frame(frame)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Tab / Shift+Tab: move through the controls, top to bottom: \
+                          resume prompt, sliders, Options, Estimates, session buttons, History");
+                ui.label("Enter / Space: activate the focused control");
+                ui.label("Arrow keys: adjust the focused slider");
+                ui.separator();
+                // Straight from the table the key handling uses, so only keys that work now show up
+                let mut unfocused = false;
+                for shortcut in SHORTCUTS.iter().filter(|shortcut| self.shortcut_active(shortcut.scope, false)) {
+                    let keys = shortcut.label(&self.settings.key_bindings);
+                    if keys.is_empty() {
+                        continue; // Unbound in the settings
+                    }
+                    if shortcut.scope == Scope::Unfocused && !unfocused {
+                        unfocused = true;
+                        ui.label("With nothing focused:");
+                    }
+                    ui.label(format!("{}: {}", keys, shortcut.description));
+                }
+            });
+        self.show_keyboard_help &= open;
+    }
+
+    // Before anything is drawn, so the frame with the wake-up input already shows the full panel
+    fn track_input(&mut self, ctx: &egui::Context) {
+        let active = ctx.input(|i| {
+            let moved = i.pointer.delta() != egui::Vec2::ZERO || i.pointer.any_down();
+            let event = i.events.iter().any(|event| {
+                matches!(event, egui::Event::Key { .. } | egui::Event::MouseWheel { .. } | egui::Event::Touch { .. })
+            });
+            moved || event
+        });
+        if active {
+            self.last_input = Instant::now();
+        }
+    }
+
+    // Out of the way when watched from across the room or dimmed, back on any movement or key
+    // press. Set every frame, so the cursor returns by itself once fullscreen ends.
+    fn hide_idle_cursor(&mut self, ctx: &egui::Context) {
+        let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+        let popup = ctx.memory(|m| m.any_popup_open())
+            || self.confirming_stop
+            || self.confirming_start
+            || self.show_keyboard_help
+            || self.palette_query.is_some();
+        if (fullscreen || self.dimmed()) && !popup && self.last_input.elapsed() >= CURSOR_HIDE_AFTER {
+            ctx.set_cursor_icon(egui::CursorIcon::None);
+        }
+    }
+
+    // Above everything else, takeover included, so a preview can't pass for a real workout
+    fn preview_banner_ui(&mut self, ctx: &egui::Context) {
+        let frame = egui::Frame::side_top_panel(&ctx.style()).fill(egui::Color32::from_rgb(0xC0, 0x1C, 0x28));
+        egui::TopBottomPanel::top("preview_banner").frame(frame).show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.label(
+                    egui::RichText::new(format!("PREVIEW {}× SPEED", self.preview_speed))
+                        .size(24.0)
+                        .strong()
+                        .color(egui::Color32::WHITE),
+                );
+                ui.colored_label(egui::Color32::WHITE, "Not a real workout, nothing goes into the history");
+                if ui.button("Back to real time").clicked() {
+                    self.set_preview_speed(1);
+                }
+            });
+        });
+    }
+
+    // Left alone at Idle for idle_dim_minutes, but never over a ringing alarm or an open prompt
+    fn dimmed(&self) -> bool {
+        let after = Duration::from_secs(self.settings.idle_dim_minutes * 60);
+        self.settings.idle_dim_minutes > 0
+            && self.state == TimerState::Idle
+            && !self.completion_alarm
+            && !self.confirming_start
+            && self.palette_query.is_none()
+            && self.last_input.elapsed() >= after
+            && self.idle_since.elapsed() >= after
+    }
+
+    // Low-contrast stand-in for the whole window: the time of day and how the last session went
+    fn dimmed_ui(&self, ctx: &egui::Context) {
+        let frame = egui::Frame::central_panel(&ctx.style()).fill(egui::Color32::BLACK);
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+            let rect = ui.max_rect();
+            let size = (rect.width() / 6.0).min(rect.height() / 3.0);
+            let time = format::time_of_day(chrono::Local::now(), self.settings.clock_24h, self.settings.clock_seconds);
+            let painter = ui.painter();
+            painter.text(rect.center(), egui::Align2::CENTER_CENTER, time, egui::FontId::proportional(size), DIMMED_TEXT);
+            let Some(record) = self.last_session.as_ref().or(self.history.records.last()) else {
+                return;
+            };
+            let mut summary = format!(
+                "Last session: {}, {} total",
+                format::count(record.rounds as u64, "round"),
+                format::clock(record.total_secs)
+            );
+            if let Some(finished) = chrono::DateTime::from_timestamp(record.finished_at, 0) {
+                let finished = finished.with_timezone(&chrono::Local);
+                summary += &format!(", finished at {}", format::time_of_day(finished, self.settings.clock_24h, false));
+            }
+            painter.text(
+                rect.center() + egui::vec2(0.0, size * 0.6),
+                egui::Align2::CENTER_TOP,
+                summary,
+                egui::FontId::proportional((size / 5.0).max(14.0)),
+                DIMMED_TEXT,
+            );
+        });
+    }
+
+    // The last seconds of work and rest take over the whole window, unless motion is reduced
+    fn takeover_active(&self) -> bool {
+        matches!(self.state, TimerState::Workout | TimerState::Rest)
+            && (1..=TAKEOVER_SECS).contains(&self.remaining_time)
+            && !self.settings.reduced_motion
+    }
+
+    // Remembered on close only, changing the builder every frame would move the window back
+    fn remember_second_display_pos(&mut self) {
+        if let Some(pos) = self.second_display_at.take() {
+            self.ui.second_display_pos = Some([pos.x, pos.y]);
+        }
+    }
+
+    fn second_display_ui(&mut self, ctx: &egui::Context) {
+        if !self.settings.second_display {
+            self.remember_second_display_pos();
+            return;
+        }
+        let mut builder = egui::ViewportBuilder::default()
+            .with_title("Workout Timer display")
+            .with_decorations(false)
+            .with_inner_size([640.0, 360.0])
+            .with_fullscreen(self.settings.second_display_fullscreen);
+        if let Some(pos) = self.ui.second_display_pos {
+            builder = builder.with_position(pos);
+        }
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("second_display"), builder, |ctx, _| {
+            let (close, pos) = ctx.input(|i| (i.viewport().close_requested(), i.viewport().outer_rect.map(|r| r.min)));
+            if pos.is_some() {
+                self.second_display_at = pos;
+            }
+            if close {
+                self.settings.second_display = false;
+                self.remember_second_display_pos();
+                return;
+            }
+
+            let frame = egui::Frame::central_panel(&ctx.style()).fill(match self.state {
+                TimerState::Idle => egui::Color32::BLACK,
+                _ => self.phase_color(),
+            });
+            egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+                let rect = ui.max_rect();
+                // No title bar: drag to move, double-click for fullscreen
+                let response = ui.interact(rect, ui.id().with("second_display"), egui::Sense::click_and_drag());
+                if response.drag_started() {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                }
+                if response.double_clicked() {
+                    self.settings.second_display_fullscreen = !self.settings.second_display_fullscreen;
+                    self.save_settings();
+                }
+
+                let secs = if self.stopwatch() { self.stopwatch_elapsed().as_secs() } else { self.remaining_time };
+                let size = (rect.height() * 0.6).min(rect.width() / 3.2);
+                let painter = ui.painter();
+                painter.text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    format::clock(secs),
+                    self.countdown_font(size),
+                    egui::Color32::WHITE,
+                );
+                let label_size = size * 0.25;
+                painter.text(
+                    rect.center_top() + egui::vec2(0.0, label_size),
+                    egui::Align2::CENTER_CENTER,
+                    self.status_name(),
+                    egui::FontId::proportional(label_size),
+                    egui::Color32::WHITE,
+                );
+                if self.state != TimerState::Idle {
+                    painter.text(
+                        rect.center_bottom() - egui::vec2(0.0, label_size),
+                        egui::Align2::CENTER_CENTER,
+                        self.round_label(self.session_rounds),
+                        egui::FontId::proportional(label_size),
+                        egui::Color32::WHITE,
+                    );
+                }
+                self.paint_wall_clock(painter, rect, label_size * 0.6, egui::Color32::WHITE);
+            });
+        });
+    }
+
+    fn takeover_ui(&mut self, ctx: &egui::Context) {
+        let frame = egui::Frame::central_panel(&ctx.style()).fill(self.phase_color());
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+            let rect = ui.max_rect();
+            ui.interact(rect, ui.id().with("timer_area"), egui::Sense::CLICK)
+                .context_menu(|ui| self.context_menu_ui(ui));
+            let size = rect.height().min(rect.width()) * 0.8;
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                self.remaining_time.to_string(),
+                self.countdown_font(size),
+                egui::Color32::WHITE,
+            );
+            self.paint_wall_clock(ui.painter(), rect, 24.0, egui::Color32::WHITE);
+        });
+    }
+
+    // Painted rather than laid out, so it never pushes the controls around
+    fn paint_wall_clock(&self, painter: &egui::Painter, rect: egui::Rect, size: f32, color: egui::Color32) {
+        if !self.settings.show_clock {
+            return;
+        }
+        let text = format::time_of_day(chrono::Local::now(), self.settings.clock_24h, self.settings.clock_seconds);
+        let margin = size * 0.5;
+        painter.text(
+            rect.right_top() + egui::vec2(-margin, margin),
+            egui::Align2::RIGHT_TOP,
+            text,
+            egui::FontId::proportional(size),
+            color,
+        );
+    }
+
+    fn countdown_font(&self, size: f32) -> egui::FontId {
+        if self.settings.countdown_font {
+            egui::FontId::new(size, egui::FontFamily::Name(COUNTDOWN_FAMILY.into()))
+        } else {
+            egui::FontId::proportional(size)
+        }
+    }
+
+    fn background_texture(&mut self, ctx: &egui::Context) -> Option<egui::TextureHandle> {
+        let path = self.settings.background_image.trim();
+        if path != self.background_path {
+            self.background_path = path.to_string();
+            self.background = None;
+            self.background_error = None;
+            if !path.is_empty() {
+                // Keep within what every GPU can take as a single texture
+                match load_texture(ctx, "background", path, MAX_BACKGROUND_SIZE) {
+                    Ok(texture) => self.background = Some(texture),
+                    Err(err) => {
+                        log::warn!("Failed to load background image {}: {}", path, err);
+                        self.background_error = Some(format!("Background image not shown: {}", err));
+                    }
+                }
+            }
+        }
+        self.background.clone()
+    }
+
+    // Plan rounds only. Loaded on first use; the cache keeps just this round's and the next
+    // round's images, so a long plan never holds more than two textures.
+    fn exercise_image(&mut self, ctx: &egui::Context) -> Option<Result<egui::TextureHandle, String>> {
+        let image_of = |round: u32| self.planned_round(round).and_then(|round| round.image.clone());
+        let current = image_of(self.current_round);
+        let next = image_of(self.current_round + 1);
+        self.exercise_images.retain(|path, _| Some(path) == current.as_ref() || Some(path) == next.as_ref());
+        if !matches!(self.state, TimerState::Workout | TimerState::PausedWorkout) {
+            return None;
+        }
+        let path = current?;
+        let texture = self.exercise_images.entry(path.clone()).or_insert_with(|| {
+            load_texture(ctx, &path, &path, MAX_EXERCISE_IMAGE_SIZE).inspect_err(|err| {
+                log::warn!("Failed to load exercise image {}: {}", path, err);
+            })
+        });
+        Some(texture.clone())
+    }
+
+    fn exercise_image_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(texture) = self.exercise_image(ui.ctx()) else {
+            return;
+        };
+        let size = egui::Vec2::splat(EXERCISE_IMAGE_DISPLAY_SIZE);
+        ui.vertical_centered(|ui| match texture {
+            Ok(texture) => {
+                ui.add(egui::Image::new(&texture).max_size(size));
+            }
+            Err(err) => {
+                let (rect, response) = ui.allocate_exact_size(egui::Vec2::new(size.x, size.y / 2.0), egui::Sense::hover());
+                ui.painter().rect_stroke(rect, 4.0, ui.visuals().widgets.noninteractive.bg_stroke, egui::StrokeKind::Inside);
+                ui.painter().text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "No image",
+                    egui::FontId::proportional(14.0),
+                    ui.visuals().weak_text_color(),
+                );
+                response.on_hover_text(err);
+            }
+        });
+    }
+
+    // Scaled to cover the whole panel, then darkened so the text on top stays readable
+    fn paint_background(&mut self, ui: &egui::Ui) {
+        let Some(texture) = self.background_texture(ui.ctx()) else {
+            return;
+        };
+        let rect = ui.max_rect();
+        ui.painter().image(texture.id(), rect, cover_uv(texture.size_vec2(), rect.size()), egui::Color32::WHITE);
+        let dim = egui::Color32::from_black_alpha((self.settings.background_dim * 255.0) as u8);
+        ui.painter().rect_filled(rect, 0.0, dim);
+    }
+
+    // Click a key to remap it, the next key pressed takes its place. Escape cancels.
+    fn key_bindings_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        if let Some(binding) = self.capturing_key {
+            let pressed = ui.input(|i| {
+                i.events.iter().find_map(|event| match event {
+                    egui::Event::Key { key, pressed: true, repeat: false, .. } => Some(*key),
+                    _ => None,
+                })
+            });
+            if let Some(key) = pressed {
+                self.capturing_key = None;
+                if key != egui::Key::Escape {
+                    match shortcuts::conflict(&self.settings.key_bindings, binding, key) {
+                        Some(other) => {
+                            self.key_binding_error =
+                                Some(format!("{} is already used to {}", key.symbol_or_name(), other.description));
+                        }
+                        None => {
+                            self.settings.key_bindings.insert(binding.to_string(), key.name().to_string());
+                            self.key_binding_error = None;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        egui::Grid::new("key_bindings").num_columns(3).show(ui, |ui| {
+            for shortcut in SHORTCUTS {
+                let Some(binding) = shortcut.binding else {
+                    continue;
+                };
+                ui.label(shortcut.title());
+                let text = if self.capturing_key == Some(binding) {
+                    "Press a key…".to_string()
+                } else {
+                    match shortcut.label(&self.settings.key_bindings) {
+                        keys if keys.is_empty() => "None".to_string(),
+                        keys => keys,
+                    }
+                };
+                let response = ui.button(text);
+                if response.clicked() {
+                    // Space or Enter would click the focused button again instead of being captured
+                    response.surrender_focus();
+                    self.capturing_key = Some(binding);
+                    self.key_binding_error = None;
+                }
+                if ui.button("Clear").on_hover_text("No key for this").clicked() {
+                    self.settings.key_bindings.insert(binding.to_string(), String::new());
+                    changed = true;
+                }
+                ui.end_row();
+            }
+        });
+
+        if ui.add_enabled(!self.settings.key_bindings.is_empty(), egui::Button::new("Reset to defaults")).clicked() {
+            self.settings.key_bindings.clear();
+            self.key_binding_error = None;
+            changed = true;
+        }
+        let problems = shortcuts::problems(&self.settings.key_bindings);
+        for problem in self.key_binding_error.iter().chain(&problems) {
+            ui.colored_label(ui.visuals().warn_fg_color, problem);
+        }
+        changed
+    }
+
+    fn plan_ui(&mut self, ui: &mut egui::Ui) {
+        path_field(ui, "Plan file", &mut self.settings.plan_file, "TOML file with [[block]] entries");
+        ui.horizontal(|ui| {
+            let idle = self.state == TimerState::Idle;
+            let has_path = !self.settings.plan_file.trim().is_empty();
+            let label = if self.plan.is_some() { "Reload" } else { "Load" };
+            if ui.add_enabled(idle && has_path, egui::Button::new(label)).clicked() {
+                self.load_plan(self.settings.plan_file.trim().to_string());
+            }
+            if ui.add_enabled(idle && self.plan.is_some(), egui::Button::new("Clear")).clicked() {
+                self.plan = None;
+            }
+        });
+        if let Some(plan) = &self.plan {
+            ui.label(format!(
+                "{}, {}, {} in total",
+                format::count(plan.blocks as u64, "block"),
+                format::count(plan.rounds.len() as u64, "round"),
+                format::clock(self.session_total_secs())
+            ));
+            ui.label("The plan replaces the workout, rest and rounds settings");
+        }
+        if let Some(err) = &self.plan_error {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+    }
+
+    // Station names and how many laps go round them, a break to rotate after each station
+    fn stations_ui(&mut self, ui: &mut egui::Ui, controls: Controls) {
+        let mut changed = ui.checkbox(&mut self.settings.use_stations, "Rotate through stations, one per round").changed();
+        if self.settings.use_stations && self.settings.mode != TimerMode::Intervals {
+            ui.weak("Only in Intervals mode");
+        } else if self.settings.use_stations && self.round_list().is_some() {
+            ui.weak("The round list or a loaded plan takes precedence");
+        }
+        // The running session reads its rounds from here
+        let editable = self.state == TimerState::Idle || self.stations().is_none();
+        ui.add_enabled_ui(editable, |ui| {
+            let mut laps = self.settings.laps as u64;
+            if number_slider(ui, &mut laps, 1..=MAX_LAPS as u64, "Laps", false, controls) {
+                self.settings.laps = laps as u32;
+                changed = true;
+            }
+            changed |= number_slider(ui, &mut self.settings.rotate_break, 2..=MAX_PHASE_SECS, "Rotate break (sec)", true, controls);
+            let mut delete = None;
+            for (index, station) in self.settings.stations.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}.", index + 1));
+                    changed |= ui.add(egui::TextEdit::singleline(station).desired_width(140.0)).changed();
+                    if ui.small_button("Delete").clicked() {
+                        delete = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = delete {
+                self.settings.stations.remove(index);
+                changed = true;
+            }
+            if ui.button("Add station").clicked() {
+                self.settings.stations.push(format!("Station {}", self.settings.stations.len() + 1));
+                changed = true;
+            }
+        });
+        if changed {
+            self.save_settings();
+        }
+    }
+
+    // Per-round editor, rows reorder by dragging the handle
+    fn round_list_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = ui.checkbox(&mut self.settings.use_round_list, "Use the round list instead of the sliders").changed();
+        if self.plan.is_some() {
+            ui.weak("A loaded plan file takes precedence");
+        }
+        // The running session reads its rounds from this list
+        let editable = self.state == TimerState::Idle || !self.settings.use_round_list;
+        ui.add_enabled_ui(editable, |ui| {
+            let mut moved = None;
+            let mut duplicate = None;
+            let mut delete = None;
+            for (index, round) in self.settings.round_list.iter_mut().enumerate() {
+                let row = ui.horizontal(|ui| {
+                    ui.dnd_drag_source(ui.id().with(("round_row", index)), index, |ui| {
+                        ui.label("☰");
+                    });
+                    changed |= ui.add(egui::TextEdit::singleline(&mut round.name).desired_width(100.0)).changed();
+                    changed |= ui.add(egui::DragValue::new(&mut round.work).range(1..=MAX_PHASE_SECS).suffix("s work")).changed();
+                    changed |= ui.add(egui::DragValue::new(&mut round.rest).range(0..=MAX_PHASE_SECS).suffix("s rest")).changed();
+                    if ui.small_button("Duplicate").clicked() {
+                        duplicate = Some(index);
+                    }
+                    if ui.small_button("Delete").clicked() {
+                        delete = Some(index);
+                    }
+                }).response;
+                if let Some(from) = row.dnd_release_payload::<usize>() {
+                    moved = Some((*from, index));
+                } else if row.dnd_hover_payload::<usize>().is_some() {
+                    ui.painter().hline(row.rect.x_range(), row.rect.top(), ui.visuals().selection.stroke);
+                }
+            }
+            let rounds = &mut self.settings.round_list;
+            if let Some((from, to)) = moved
+                && from != to
+            {
+                let round = rounds.remove(from);
+                rounds.insert(to, round);
+                changed = true;
+            }
+            if let Some(index) = duplicate {
+                rounds.insert(index + 1, rounds[index].clone());
+                changed = true;
+            }
+            if let Some(index) = delete {
+                rounds.remove(index);
+                changed = true;
+            }
+            if ui.button("Add round").clicked() {
+                let next = rounds.last().cloned().unwrap_or(PlanRound {
+                    name: format!("Round {}", rounds.len() + 1),
+                    work: self.settings.workout_duration,
+                    rest: self.settings.rest_duration,
+                    image: None,
+                });
+                rounds.push(next);
+                changed = true;
+            }
+        });
+        if changed {
+            self.save_settings();
+        }
+    }
+
+    fn summary_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(record) = &self.last_session else {
+            return;
+        };
+        let mut close = false;
+        ui.group(|ui| {
+            if self.settings.show_completion_message && !self.settings.completion_message.trim().is_empty() {
+                let message =
+                    quotes::completion_message(&self.settings.completion_message, record.rounds, &format::clock(record.total_secs));
+                ui.label(egui::RichText::new(message).heading());
+            }
+            ui.strong("Session summary");
+            ui.label(format!(
+                "{}, {} total, {} work, {}",
+                format::count(record.rounds as u64, "round"),
+                format::clock(record.total_secs),
+                format::clock(record.work_secs),
+                format::count(record.pauses as u64, "pause")
+            ));
+            if record.paused_secs > 0 {
+                ui.label(format!("Paused total: {}", format::clock(record.paused_secs)));
+            }
+            if record.planned_secs > 0 {
+                ui.label(format!(
+                    "Took {}, {} planned",
+                    format::clock(record.total_secs),
+                    format::clock(record.planned_secs)
+                ));
+            }
+            if !record.lap_secs.is_empty() {
+                let laps: Vec<String> = record.lap_secs.iter().map(|secs| format::clock(*secs as u64)).collect();
+                ui.label(format!("Laps: {}", laps.join(", ")));
+            }
+            if record.overtime_secs > 0 {
+                ui.label(format!("Overtime: +{}", format::clock(record.overtime_secs)));
+            }
+            for line in history::planned_vs_actual(record) {
+                ui.label(line);
+            }
+            if let Some(kcal) = record.kcal {
+                ui.label(format!("About {:.0} kcal", kcal));
+            }
+            if !record.tag.is_empty() {
+                ui.label(format!("Tag: {}", record.tag));
+            }
+            ui.label(history::comparison_text(record, self.history.previous_comparable(record)));
+            close = ui.button("Close").clicked();
+        });
+        if close {
+            self.last_session = None;
+        }
+    }
+
+    // One column per week and a square per day, shaded by the work minutes of that day
+    fn year_heatmap_ui(&self, ui: &mut egui::Ui) {
+        let today = chrono::Local::now().date_naive();
+        let first = history::week_start(today, self.settings.week_start) - chrono::Days::new(7 * (HEATMAP_WEEKS - 1));
+        let days = self.history.daily_work_secs(self.ui.history_filter.as_deref(), self.pomodoro(), first);
+        if days.is_empty() {
+            ui.weak("No sessions in the past year yet");
+        }
+        let most = days.values().copied().max().unwrap_or(0).max(1);
+
+        let step = HEATMAP_CELL + HEATMAP_GAP;
+        let label_height = 14.0;
+        let size = egui::Vec2::new(HEATMAP_WEEKS as f32 * step, label_height + 7.0 * step);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let empty = ui.visuals().widgets.inactive.bg_fill;
+        let grid_top = rect.top() + label_height;
+
+        for week in 0..HEATMAP_WEEKS {
+            let x = rect.left() + week as f32 * step;
+            for weekday in 0..7 {
+                let date = first + chrono::Days::new(week * 7 + weekday);
+                if date > today {
+                    break;
+                }
+                // Month names go above the week that holds the 1st
+                if date.day() == 1 {
+                    painter.text(
+                        egui::Pos2::new(x, rect.top()),
+                        egui::Align2::LEFT_TOP,
+                        date.format("%b").to_string(),
+                        egui::FontId::proportional(11.0),
+                        ui.visuals().weak_text_color(),
+                    );
+                }
+                let secs = days.get(&date).copied().unwrap_or(0);
+                // Quarter steps of the busiest day, so one long session doesn't wash out the rest
+                let color = match secs {
+                    0 => empty,
+                    secs => {
+                        let level = (secs * 4).div_ceil(most).min(4) as f32 / 4.0;
+                        empty.lerp_to_gamma(GOAL_COLOR, level)
+                    }
+                };
+                let cell = egui::Rect::from_min_size(
+                    egui::Pos2::new(x, grid_top + weekday as f32 * step),
+                    egui::Vec2::splat(HEATMAP_CELL),
+                );
+                painter.rect_filled(cell, 2.0, color);
+            }
+        }
+
+        if let Some(pos) = response.hover_pos() {
+            let week = ((pos.x - rect.left()) / step) as u64;
+            let weekday = ((pos.y - grid_top) / step).floor();
+            if pos.y >= grid_top && (0.0..7.0).contains(&weekday) && week < HEATMAP_WEEKS {
+                let date = first + chrono::Days::new(week * 7 + weekday as u64);
+                if date <= today {
+                    let minutes = days.get(&date).copied().unwrap_or(0) / 60;
+                    response.on_hover_text_at_pointer(format!(
+                        "{}: {}",
+                        date.format("%a %-d %b %Y"),
+                        format::count(minutes, "work minute")
+                    ));
+                }
+            }
+        }
+    }
+
+    fn history_ui(&mut self, ui: &mut egui::Ui) {
+        if let Some((done, goal)) = self.weekly_goal_progress() {
+            let unit = match self.settings.weekly_goal_kind {
+                GoalKind::Sessions => "sessions",
+                GoalKind::WorkMinutes => "work minutes",
+            };
+            let text = format!("{}/{} {} this week", done, goal, unit);
+            if done >= goal {
+                ui.colored_label(GOAL_COLOR, text);
+            } else {
+                ui.label(text);
+            }
+            ui.add(egui::ProgressBar::new((done as f32 / goal as f32).min(1.0)));
+        }
+
+        let tags = self.history.tags();
+        egui::ComboBox::from_label("Tag filter")
+            .selected_text(self.ui.history_filter.as_deref().unwrap_or("All"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.ui.history_filter, None, "All");
+                for tag in tags {
+                    ui.selectable_value(&mut self.ui.history_filter, Some(tag.clone()), tag);
+                }
+            });
+
+        let totals = self.history.totals(self.ui.history_filter.as_deref(), self.pomodoro(), chrono::Local::now().date_naive());
+        ui.label(format!("Sessions: {}", totals.sessions));
+        ui.label(format!("Work time: {} min", totals.work_secs / 60));
+        ui.label(format!("Streak: {}", format::count(totals.streak_days as u64, "day")));
+        let averages = self.history.weekly_pause_averages(
+            self.ui.history_filter.as_deref(),
+            self.pomodoro(),
+            chrono::Local::now().date_naive(),
+            self.settings.week_start,
+            PAUSE_TREND_WEEKS,
+        );
+        if averages.iter().any(Option::is_some) {
+            let weeks: Vec<String> = averages
+                .iter()
+                .map(|average| average.map_or("-".to_string(), |average| format!("{:.1}", average)))
+                .collect();
+            ui.label(format!("Pauses per session by week: {} (this week last)", weeks.join(" · ")))
+                .on_hover_text(format!("Average over each of the last {} weeks", PAUSE_TREND_WEEKS));
+        }
+
+        egui::CollapsingHeader::new("Year").show(ui, |ui| {
+            // Wider than the default window, recent weeks stay in view
+            egui::ScrollArea::horizontal().stick_to_right(true).show(ui, |ui| {
+                self.year_heatmap_ui(ui);
+            });
+        });
+
+        egui::CollapsingHeader::new("Weekly goal").show(ui, |ui| {
+            let mut changed = ui.add(egui::Slider::new(&mut self.settings.weekly_goal, 0..=600).text("Goal (0 = off)")).changed();
+            ui.horizontal(|ui| {
+                changed |= ui.radio_value(&mut self.settings.weekly_goal_kind, GoalKind::Sessions, "Sessions").changed();
+                changed |= ui.radio_value(&mut self.settings.weekly_goal_kind, GoalKind::WorkMinutes, "Work minutes").changed();
+            });
+            egui::ComboBox::from_label("Week starts on")
+                .selected_text(format!("{}", self.settings.week_start))
+                .show_ui(ui, |ui| {
+                    for day in [chrono::Weekday::Mon, chrono::Weekday::Sat, chrono::Weekday::Sun] {
+                        changed |= ui.selectable_value(&mut self.settings.week_start, day, format!("{}", day)).changed();
+                    }
+                });
+            if changed {
+                self.save_settings();
+            }
+        });
+
+        egui::CollapsingHeader::new("Backup").show(ui, |ui| {
+            if path_field(ui, "Backup file", &mut self.settings.history_backup_file, "JSON file with every recorded session") {
+                self.save_settings();
+            }
+            let path = self.settings.history_backup_file.trim().to_string();
+            ui.horizontal(|ui| {
+                let has_path = !path.is_empty();
+                if ui.add_enabled(has_path, egui::Button::new("Backup history")).clicked() {
+                    self.backup_history();
+                }
+                if ui.add_enabled(has_path, egui::Button::new("Restore history")).clicked() {
+                    self.backup_message = Some(match self.history.restore(Path::new(&path), &self.history_path) {
+                        Ok((added, skipped)) => format!(
+                            "Added {}, skipped {} already in the history",
+                            format::count(added as u64, "session"),
+                            skipped
+                        ),
+                        Err(err) => err,
+                    });
+                }
+            });
+            if let Some(message) = &self.backup_message {
+                ui.label(message);
+            }
+        });
+    }
+}
+
+impl eframe::App for WorkoutTimer {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.update();
+        let waking = self.dimmed(); // The key that wakes the panel does nothing else
+        self.track_input(ctx);
+        self.speak_due();
+        self.persist_snapshot();
+        if !waking {
+            self.handle_keyboard(ctx);
+        }
+        self.handle_gamepad(ctx);
+        self.check_settings_file();
+        self.handle_dropped_files(ctx);
+        self.update_sync();
+        self.handle_instance_messages(ctx);
+        self.hooks.poll();
+        let overlay_text = self.overlay_text();
+        self.overlay.update(&self.settings.overlay_file, &overlay_text);
+        let presence = self.presence_status();
+        self.discord.update(self.settings.discord_presence, presence);
+        self.update_mqtt(ctx);
+        let status_line = self.status_line();
+        let status_target = self.status_output_override.as_deref().unwrap_or(&self.settings.status_output);
+        self.status_output.update(status_target, status_line);
+
+        // Also picks up a theme changed in the settings file
+        let theme = self.settings.theme.preference();
+        if ctx.options(|o| o.theme_preference) != theme {
+            ctx.set_theme(theme);
+        }
+        self.dark_mode = ctx.style().visuals.dark_mode;
+        if self.touch_style != self.settings.touch_mode {
+            self.touch_style = self.settings.touch_mode;
+            apply_style(ctx, self.touch_style);
+        }
+
+        if self.minimize_on_start {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
+            self.minimize_on_start = false;
+        }
+
+        let mut panel_frame = egui::Frame::central_panel(&ctx.style());
+        let rest_pulse = self.rest_pulse();
+        if let Some(pulse) = rest_pulse {
+            panel_frame.fill = panel_frame.fill.lerp_to_gamma(REST_COLOR, 0.35 * pulse);
+        }
+
+        if self.previewing() {
+            self.preview_banner_ui(ctx);
+        }
+        let dimmed = self.dimmed();
+        if !self.laps.is_empty() && !dimmed {
+            egui::SidePanel::right("laps").resizable(false).show(ctx, |ui| {
+                self.laps_ui(ui);
+            });
+        }
+
+        if dimmed {
+            self.dimmed_ui(ctx);
+        } else if self.takeover_active() {
+            self.takeover_ui(ctx);
+        } else {
+            egui::CentralPanel::default().frame(panel_frame).show(ctx, |ui| {
+                // Added first so every widget on top keeps its own clicks. Clicks only,
+                // Tab should never land on the background.
+                let background = ui.interact(ui.max_rect(), ui.id().with("timer_area"), egui::Sense::CLICK);
+                background.context_menu(|ui| self.context_menu_ui(ui));
+                self.paint_background(ui);
+                self.paint_wall_clock(ui.painter(), ui.max_rect(), 16.0, ui.visuals().weak_text_color());
+
+                let width = ui.available_width();
+                let narrow = width < NARROW_WIDTH;
+                if narrow {
+                    let style = ui.style_mut();
+                    for font in style.text_styles.values_mut() {
+                        font.size *= NARROW_FONT_SCALE;
+                    }
+                    style.spacing.slider_width = (width - NARROW_FIELD_ROOM).max(style.spacing.interact_size.x);
+                }
+                let mut countdown_size = ui.style().text_styles[&egui::TextStyle::Body].size;
+                let mut column = ui.max_rect();
+                if width > WIDE_WIDTH {
+                    countdown_size = countdown_size.max(column.height() * WIDE_COUNTDOWN_SHARE);
+                    column = egui::Rect::from_center_size(column.center(), egui::vec2(COLUMN_WIDTH, column.height()));
+                }
+                let controls = if self.settings.touch_mode {
+                    Controls::Touch
+                } else if narrow {
+                    Controls::Narrow
+                } else {
+                    Controls::Regular
+                };
+
+                // Scroll once the content outgrows the window
+                ui.scope_builder(egui::UiBuilder::new().max_rect(column), |ui| egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.heading("Workout Interval Timer");
+                        self.mqtt_indicator_ui(ui);
+                    });
+                    if self.settings_changed_on_disk {
+                        ui.weak("Settings changed on disk, they apply once the session ends");
+                    }
+                    self.crash_notice_ui(ui);
+                    Self::file_notice_ui(ui, &mut self.settings_notice);
+                    Self::file_notice_ui(ui, &mut self.history_notice);
+                    self.update_banner_ui(ui);
+                    self.notice_ui(ui);
+                    if let Some(err) = &self.background_error {
+                        ui.colored_label(ui.visuals().warn_fg_color, err);
+                    }
+                    if self.following() {
+                        self.follower_ui(ui);
+                        return;
+                    }
+
+                    let in_round = !matches!(self.state, TimerState::Idle | TimerState::LeadUp | TimerState::PausedLeadUp);
+                    if in_round && self.is_final_round() {
+                        ui.label(
+                            egui::RichText::new("FINAL ROUND")
+                                .size(32.0)
+                                .strong()
+                                .color(egui::Color32::from_rgb(0xE0, 0x4F, 0x3A)),
+                        );
+                    }
+
+                    // Offer to pick up a session that was interrupted by a restart
+                    if let Some(snapshot) = &self.pending_resume {
+                        let mut resume = false;
+                        let mut discard = false;
+                        ui.group(|ui| {
+                            ui.label(format!(
+                                "Resume previous session? {}, {} left in the phase",
+                                format::round_progress(
+                                    snapshot.current_round + 1,
+                                    snapshot.rounds,
+                                    self.settings.count_rounds_down
+                                ),
+                                format::clock(snapshot.remaining_time)
+                            ));
+                            ui.horizontal(|ui| {
+                                resume = ui.button("Resume").clicked();
+                                discard = ui.button("Discard").clicked();
+                            });
+                        });
+                        if resume {
+                            if let Some(snapshot) = self.pending_resume.take() {
+                                self.restore_snapshot(snapshot);
+                            }
+                        } else if discard {
+                            self.pending_resume = None;
+                            SessionSnapshot::delete();
+                        }
+                    }
+
+                    // Check if fanfare is active, it never draws over a running session
+                    if self.state == TimerState::Idle && let Some(start_time) = self.fanfare_start_time {
+                        let elapsed = start_time.elapsed().as_secs_f32();
+                        if elapsed < 2.0 || self.completion_alarm {
+                            // Display fanfare message
+                            ui.vertical(|ui| {
+                                ui.label(format!(
+                                    "Congratulations, you completed {}!",
+                                    format::count(self.session_rounds as u64, "round")
+                                ));
+                                if self.goal_reached_fanfare {
+                                    ui.colored_label(egui::Color32::from_rgb(0x3B, 0xA4, 0x58), "Weekly goal reached!");
+                                }
+
+                                // Display three spinning stars, five when the weekly goal was just reached
+                                // (standing still in battery saver)
+                                let angle = if self.settings.battery_saver {
+                                    0.0
+                                } else {
+                                    elapsed * 2.0 * std::f32::consts::PI // Rotate 360 degrees per second
+                                };
+                                if let Some(texture) = &self.star_texture {
+                                    ui.horizontal(|ui| {
+                                        let stars = if self.goal_reached_fanfare { 5 } else { 3 };
+                                        for _ in 0..stars {
+                                            ui.add(egui::Image::new(texture).rotate(angle, egui::Vec2::new(0.5, 0.5)));
+                                        }
+                                    });
+                                }
+
+                                if self.completion_alarm && ui.button("Dismiss").clicked() {
+                                    self.silence();
+                                }
+                            });
+                        } else {
+                            self.fanfare_start_time = None; // End fanfare
+                        }
+                    }
+
+                    if self.state == TimerState::Idle {
+                        self.summary_ui(ui);
+                    }
+
+                    let slider_width = ui.available_width();
+
+                    let mut changed = false;
+
+                    let step = self.settings.snap_step.max(1);
+                    if !self.undo.is_empty() || !self.redo.is_empty() {
+                        ui.horizontal(|ui| {
+                            let idle = self.state == TimerState::Idle;
+                            let why = |nothing| if idle { nothing } else { "Not during a session" };
+                            if ui.add_enabled(self.can_undo(), egui::Button::new("Undo"))
+                                .on_hover_text(shortcuts::label_of(Action::Undo, &self.settings.key_bindings))
+                                .on_disabled_hover_text(why("Nothing to undo"))
+                                .clicked()
+                            {
+                                self.undo_settings();
+                            }
+                            if ui.add_enabled(self.can_redo(), egui::Button::new("Redo"))
+                                .on_hover_text(shortcuts::label_of(Action::Redo, &self.settings.key_bindings))
+                                .on_disabled_hover_text(why("Nothing to redo"))
+                                .clicked()
+                            {
+                                self.redo_settings();
+                            }
+                        });
+                    }
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label("Mode");
+                        changed |= ui.radio_value(&mut self.settings.mode, TimerMode::Intervals, "Intervals").changed();
+                        changed |= ui.radio_value(&mut self.settings.mode, TimerMode::Pomodoro, "Pomodoro").changed();
+                        changed |= ui.radio_value(&mut self.settings.mode, TimerMode::Stopwatch, "Stopwatch")
+                            .on_hover_text("Counts up until Finish, with laps")
+                            .changed();
+                    });
+
+                    if !self.stopwatch() {
+                        let (work_label, rest_label) = if self.pomodoro() {
+                            ("Focus (sec)", "Break (sec)")
+                        } else {
+                            ("Workout (sec)", "Rest (sec)")
+                        };
+                        let work_changed =
+                            duration_slider(ui, &mut self.settings.workout_duration, 2..=MAX_PHASE_SECS, step, work_label, controls);
+                        let rest_changed =
+                            duration_slider(ui, &mut self.settings.rest_duration, 2..=MAX_PHASE_SECS, step, rest_label, controls);
+                        if rest_changed {
+                            self.settings.work_rest_ratio = None; // Set by hand, no longer follows the workout
+                        }
+                        let mut ratio_changed = false;
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label("Ratio");
+                            ratio_changed |= ui.radio_value(&mut self.settings.work_rest_ratio, None, "Off").changed();
+                            for ratio in RATIO_PRESETS {
+                                let label = format!("{}:{}", ratio[0], ratio[1]);
+                                ratio_changed |= ui.radio_value(&mut self.settings.work_rest_ratio, Some(ratio), label).changed();
+                            }
+                        });
+                        if let Some(ratio) = self.settings.work_rest_ratio {
+                            let (rest, clamped) = ratio_rest(self.settings.workout_duration, ratio, 2..=MAX_PHASE_SECS);
+                            if work_changed || ratio_changed {
+                                self.settings.rest_duration = rest;
+                            }
+                            if clamped {
+                                ui.colored_label(ui.visuals().warn_fg_color, format!("Rest limited to {}s", rest));
+                            }
+                        }
+                        changed |= work_changed || rest_changed || ratio_changed;
+                        if self.pomodoro() {
+                            changed |= duration_slider(
+                                ui,
+                                &mut self.settings.long_break_duration,
+                                2..=MAX_PHASE_SECS,
+                                step,
+                                "Long break (sec)",
+                                controls,
+                            );
+                            let every = if narrow { "Long break every" } else { "Long break every (rounds, 0 = never)" };
+                            changed |= ui.add_sized(
+                                [slider_width, 20.0],
+                                egui::Slider::new(&mut self.settings.long_break_every, 0..=10).text(every),
+                            ).changed();
+                        }
+
+                        let mut rounds = self.settings.rounds as u64;
+                        if number_slider(ui, &mut rounds, 1..=50, "Rounds", false, controls) {
+                            self.settings.rounds = rounds as u32;
+                            changed = true;
+                        }
+                    }
+
+                    changed |= number_slider(
+                        ui,
+                        &mut self.settings.lead_up_duration,
+                        0..=MAX_LEAD_UP_SECS,
+                        "Lead-up (sec)",
+                        true,
+                        controls,
+                    );
+
+                    egui::CollapsingHeader::new("Plan").show(ui, |ui| {
+                        self.plan_ui(ui);
+                    });
+
+                    egui::CollapsingHeader::new("Round list").show(ui, |ui| {
+                        self.round_list_ui(ui);
+                    });
+
+                    egui::CollapsingHeader::new("Stations").show(ui, |ui| {
+                        self.stations_ui(ui, controls);
+                    });
+
+                    egui::CollapsingHeader::new("Share").show(ui, |ui| {
+                        self.share_ui(ui);
+                    });
+
+                    egui::CollapsingHeader::new("Options").show(ui, |ui| {
+                        changed |= ui.checkbox(
+                            &mut self.settings.repeat_complete_until_dismissed,
+                            "Repeat completion sound until dismissed",
+                        ).changed();
+
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.settings.show_completion_message, "Completion message").changed();
+                            changed |= ui
+                                .add_enabled(
+                                    self.settings.show_completion_message,
+                                    egui::TextEdit::singleline(&mut self.settings.completion_message),
+                                )
+                                .on_hover_text("{rounds} and {time} are replaced with the session's rounds and length")
+                                .changed();
+                        });
+                        changed |= ui.checkbox(&mut self.settings.rest_quotes, "Quotes during rest").changed();
+                        if self.settings.rest_quotes {
+                            changed |= path_field(
+                                ui,
+                                "Quotes file",
+                                &mut self.settings.quotes_file,
+                                "Text file with one quote per line, added to the built-in quotes",
+                            );
+                        }
+
+                        ui.horizontal(|ui| {
+                            egui::ComboBox::from_label("Sounds")
+                                .selected_text(sounds::pack(&self.settings.sound_pack).name)
+                                .show_ui(ui, |ui| {
+                                    for pack in sounds::SOUND_PACKS {
+                                        changed |= ui.selectable_value(&mut self.settings.sound_pack, pack.name.to_string(), pack.name).changed();
+                                    }
+                                });
+                            let idle = self.state == TimerState::Idle;
+                            if ui.add_enabled(idle, egui::Button::new("Preview")).clicked() {
+                                self.play_sound(Cue::WorkStart);
+                            }
+                        });
+
+                        egui::CollapsingHeader::new("Volume").show(ui, |ui| {
+                            let idle = self.state == TimerState::Idle;
+                            egui::Grid::new("volumes").show(ui, |ui| {
+                                let settings = &mut self.settings;
+                                let rows = [
+                                    ("Master", &mut settings.volume),
+                                    ("Phase cues", &mut settings.cue_volume),
+                                    ("Countdown beeps", &mut settings.beep_volume),
+                                    ("Completion", &mut settings.complete_volume),
+                                ];
+                                let mut preview = None;
+                                for (index, (label, volume)) in rows.into_iter().enumerate() {
+                                    ui.label(label);
+                                    changed |= ui.add(egui::Slider::new(volume, 0..=100).suffix("%")).changed();
+                                    if ui.add_enabled(idle, egui::Button::new("Preview")).clicked() {
+                                        preview = Some(index);
+                                    }
+                                    ui.end_row();
+                                }
+                                // Previews play at the levels just set, so tuning can be done by ear
+                                match preview {
+                                    Some(0 | 1) => self.play_cue(Cue::WorkStart, false),
+                                    Some(2) => self.play_tone(self.settings.countdown_base_freq),
+                                    Some(_) => self.play_cue(Cue::Complete, false),
+                                    None => {}
+                                }
+                            });
+                        });
+
+                        egui::CollapsingHeader::new("Custom sounds").show(ui, |ui| {
+                            let mut sounds_changed = false;
+                            let errors = self.custom_sound_errors.clone();
+                            for ((label, path), error) in self.custom_sound_paths().into_iter().zip(errors) {
+                                sounds_changed |= path_field(ui, label, path, "MP3, WAV, OGG or FLAC file, empty uses the sound pack");
+                                if let Some(err) = error {
+                                    ui.colored_label(egui::Color32::RED, err);
+                                }
+                            }
+                            if sounds_changed {
+                                self.check_custom_sounds();
+                                changed = true;
+                            }
+                        });
+
+                        changed |= ui.checkbox(&mut self.settings.announce_rounds, "Announce rounds").changed();
+                        changed |= ui.checkbox(&mut self.settings.speak_exercises, "Speak exercise names")
+                            .on_hover_text("Says the round name at work start and \"Next: …\" during rest, with the system's text-to-speech")
+                            .changed();
+                        if self.settings.announce_rounds {
+                            changed |= path_field(
+                                ui,
+                                "Voice clips",
+                                &mut self.settings.voice_dir,
+                                "Folder with round.wav and 1.wav to 50.wav (.ogg and .mp3 work too)",
+                            );
+                        }
+
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.settings.countdown_beeps, "Countdown beeps")
+                                .on_hover_text("Rising pitch before work, falling before rest")
+                                .changed();
+                            changed |= ui.add_enabled(
+                                self.settings.countdown_beeps,
+                                egui::DragValue::new(&mut self.settings.countdown_base_freq).range(200.0..=2000.0).suffix(" Hz"),
+                            ).changed();
+                        });
+
+                        changed |= ui.add(
+                            egui::Slider::new(&mut self.settings.rest_extension, 5..=120)
+                                .text("Rest extension (sec)"),
+                        ).changed();
+
+                        changed |= ui.add(
+                            egui::Slider::new(&mut self.settings.pre_alert_seconds, 0..=60)
+                                .text("Pre-alert before phase end (sec, 0 = off)"),
+                        ).changed();
+
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.settings.switch_sides, "Switch sides cue at")
+                                .on_hover_text("For unilateral exercises, once per work phase")
+                                .changed();
+                            changed |= ui.add_enabled(
+                                self.settings.switch_sides,
+                                egui::DragValue::new(&mut self.settings.switch_split_percent).range(10..=90).suffix(" %"),
+                            ).changed();
+                            changed |= ui.add_enabled(
+                                self.settings.switch_sides,
+                                egui::Checkbox::new(&mut self.settings.switch_flash, "Flash SWITCH"),
+                            ).changed();
+                        });
+
+                        changed |= ui.add(
+                            egui::Slider::new(&mut self.settings.auto_resume_after, 0..=600)
+                                .text("Auto-resume after (sec, 0 = never)"),
+                        ).changed();
+
+                        changed |= ui.checkbox(&mut self.settings.confirm_stop, "Ask before stopping a session").changed();
+                        changed |= ui.add(
+                            egui::Slider::new(&mut self.settings.session_length_warning_minutes, 0..=MAX_SESSION_WARNING_MINUTES)
+                                .text("Ask before starting sessions longer than (min, 0 = never)"),
+                        ).changed();
+                        changed |= ui.checkbox(&mut self.settings.manual_advance, "Wait for Next at the end of each phase")
+                            .on_hover_text("Work and rest count up past 0:00 until you press Next")
+                            .changed();
+                        changed |= ui.checkbox(&mut self.settings.battery_saver, "Battery saver")
+                            .on_hover_text("Redraw once per second and skip animations")
+                            .changed();
+                        changed |= ui.add(
+                            egui::Slider::new(&mut self.settings.idle_dim_minutes, 0..=MAX_IDLE_DIM_MINUTES)
+                                .text("Dim when idle for (min, 0 = never)"),
+                        ).on_hover_text("Shows only the time of day and the last session until the next input").changed();
+                        let has_recent = !self.ui.recent_configs.is_empty();
+                        if ui.add_enabled(has_recent, egui::Button::new("Clear recent workouts"))
+                            .on_hover_text("Forget the quick-start buttons under Start")
+                            .clicked()
+                        {
+                            self.ui.recent_configs.clear();
+                        }
+
+                        changed |= ui.checkbox(&mut self.settings.start_minimized, "Start minimized").changed();
+
+                        ui.add_enabled_ui(cfg!(feature = "update-check"), |ui| {
+                            changed |= ui.checkbox(&mut self.settings.check_for_updates, "Check for updates")
+                                .on_hover_text("Once a day at startup, asks GitHub for the latest release")
+                                .on_disabled_hover_text("This build has no update check")
+                                .changed();
+                        });
+
+                        changed |= ui.checkbox(&mut self.settings.touch_mode, "Touch mode")
+                            .on_hover_text("Big buttons and +/– steppers instead of sliders")
+                            .changed();
+
+                        ui.horizontal(|ui| {
+                            changed |= ui.checkbox(&mut self.settings.show_clock, "Clock").changed();
+                            ui.add_enabled_ui(self.settings.show_clock, |ui| {
+                                changed |= ui.checkbox(&mut self.settings.clock_24h, "24-hour").changed();
+                                changed |= ui.checkbox(&mut self.settings.clock_seconds, "Seconds").changed();
+                            });
+                        });
+
+                        ui.add_enabled_ui(GamepadInput::available(), |ui| {
+                            changed |= ui.checkbox(&mut self.settings.gamepad, "Gamepad control")
+                                .on_hover_text("A starts and pauses, B stops, RB skips the phase, the D-pad adjusts a paused timer")
+                                .on_disabled_hover_text("This build has no gamepad support")
+                                .changed();
+                        });
+
+                        changed |= ui.checkbox(&mut self.settings.countdown_font, "Monospaced countdown digits").changed();
+
+                        if path_field(ui, "Fallback font", &mut self.settings.fallback_font, "TTF, OTF or TTC file for Chinese, Japanese, Korean and other scripts, empty looks for a system font") {
+                            self.font_error = install_fonts(ui.ctx(), &self.settings.fallback_font);
+                            changed = true;
+                        }
+                        if let Some(err) = &self.font_error {
+                            ui.colored_label(ui.visuals().warn_fg_color, err);
+                        }
+
+                        ui.add_enabled_ui(self.state == TimerState::Idle, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("LAN sync");
+                                changed |= ui.radio_value(&mut self.settings.sync_mode, SyncMode::Off, "Off").changed();
+                                changed |= ui.radio_value(&mut self.settings.sync_mode, SyncMode::Leader, "Lead")
+                                    .on_hover_text("Other timers on the network can follow this one")
+                                    .changed();
+                                changed |= ui.radio_value(&mut self.settings.sync_mode, SyncMode::Follower, "Follow")
+                                    .on_hover_text("Show the leader's timer instead of running one here")
+                                    .changed();
+                                changed |= ui.add(egui::DragValue::new(&mut self.settings.sync_port).range(1024..=65535).prefix("port ")).changed();
+                            });
+                        });
+
+                        changed |= ui.checkbox(&mut self.settings.second_display, "Second display window")
+                            .on_hover_text("Only the big timer, for a TV or projector. Drag to move, double-click for fullscreen.")
+                            .changed();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Theme");
+                            changed |= ui.radio_value(&mut self.settings.theme, Theme::System, "Follow system").changed();
+                            changed |= ui.radio_value(&mut self.settings.theme, Theme::Dark, "Dark").changed();
+                            changed |= ui.radio_value(&mut self.settings.theme, Theme::Light, "Light").changed();
+                        });
+
+                        changed |= ui.checkbox(&mut self.settings.reduced_motion, "Reduce motion")
+                            .on_hover_text("No pulsing or flashing effects")
+                            .changed();
+
+                        ui.horizontal(|ui| {
+                            ui.label("Slider step (sec)");
+                            for step in [1, 5, 10, 15] {
+                                changed |= ui.radio_value(&mut self.settings.snap_step, step, step.to_string()).changed();
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Progress bar");
+                            changed |= ui.radio_value(&mut self.settings.progress_direction, ProgressDirection::FillUp, "Fill up").changed();
+                            changed |= ui.radio_value(&mut self.settings.progress_direction, ProgressDirection::DrainDown, "Drain down").changed();
+                        });
+                        changed |= ui.checkbox(&mut self.settings.count_rounds_down, "Show rounds left")
+                            .on_hover_text("\"3 rounds left\" instead of \"Round 7/10\"")
+                            .changed();
+                        changed |= ui.checkbox(&mut self.settings.session_progress, "Session progress bar")
+                            .on_hover_text("Progress of the whole session, with a tick at each round")
+                            .changed();
+
+                        changed |= path_field(
+                            ui,
+                            "Background image",
+                            &mut self.settings.background_image,
+                            "PNG or JPEG shown behind the timer, leave empty for the plain background",
+                        );
+                        changed |= ui.add_enabled(
+                            !self.settings.background_image.trim().is_empty(),
+                            egui::Slider::new(&mut self.settings.background_dim, 0.0..=1.0).text("Background darkening"),
+                        ).changed();
+
+                        changed |= path_field(
+                            ui,
+                            "Overlay file",
+                            &mut self.settings.overlay_file,
+                            "Live timer status for OBS text sources, leave empty to disable",
+                        );
+                        changed |= ui.checkbox(&mut self.settings.overlay_json, "Write overlay as JSON").changed();
+
+                        changed |= path_field(
+                            ui,
+                            "Status output",
+                            &mut self.settings.status_output,
+                            "JSON status line every second to a pipe path, or - for stdout",
+                        );
+
+                        changed |= ui.checkbox(&mut self.settings.hooks_enabled, "Run commands on phase changes")
+                            .on_hover_text("{round} and {phase} are replaced in the commands")
+                            .changed();
+                        if self.settings.hooks_enabled {
+                            for (label, command) in [
+                                ("Work start", &mut self.settings.work_start_command),
+                                ("Rest start", &mut self.settings.rest_start_command),
+                                ("Complete", &mut self.settings.complete_command),
+                            ] {
+                                ui.horizontal(|ui| {
+                                    ui.label(label);
+                                    changed |= ui.text_edit_singleline(command).changed();
+                                });
+                            }
+                            if let Some(err) = &self.hooks.last_error {
+                                ui.colored_label(egui::Color32::RED, format!("Last command error: {}", err));
+                            }
+                        }
+
+                        ui.add_enabled_ui(DiscordPresence::available(), |ui| {
+                            changed |= ui.checkbox(&mut self.settings.discord_presence, "Show status on Discord")
+                                .on_disabled_hover_text("This build has no Discord application id")
+                                .changed();
+                        });
+
+                        ui.add_enabled_ui(MqttPublisher::available(), |ui| {
+                            changed |= ui.checkbox(&mut self.settings.mqtt_enabled, "Publish to MQTT")
+                                .on_hover_text("Retained JSON on <prefix>/state at each phase change and every 10 seconds")
+                                .on_disabled_hover_text("This build has no MQTT support")
+                                .changed();
+                        });
+                        if self.settings.mqtt_enabled {
+                            egui::Grid::new("mqtt").num_columns(2).show(ui, |ui| {
+                                for (label, value, password) in [
+                                    ("Broker", &mut self.settings.mqtt_broker, false),
+                                    ("Username", &mut self.settings.mqtt_username, false),
+                                    ("Password", &mut self.settings.mqtt_password, true),
+                                    ("Topic prefix", &mut self.settings.mqtt_topic, false),
+                                ] {
+                                    ui.label(label);
+                                    changed |= ui.add(egui::TextEdit::singleline(value).password(password)).changed();
+                                    ui.end_row();
+                                }
+                            });
+                            if let Err(err) = mqtt::parse_broker(&self.settings.mqtt_broker) {
+                                ui.colored_label(ui.visuals().warn_fg_color, err);
+                            }
+                        }
+                    });
+
+                    egui::CollapsingHeader::new("Keys").show(ui, |ui| {
+                        changed |= self.key_bindings_ui(ui);
+                    });
+
+                    egui::CollapsingHeader::new("Estimates").show(ui, |ui| {
+                        self.estimates_ui(ui);
+                    });
+
+                    // Save settings if any slider value changed
+                    if changed {
+                        self.save_settings();
+                    }
+
+                    match self.state {
+                        TimerState::Idle => {
+                            controls_row(ui, controls, |ui| {
+                                if ui.button("Start").clicked() {
+                                    self.request_start();
+                                }
+                                if ui.button("Test sound").on_hover_text("Play a cue to check the output and volume").clicked() {
+                                    self.play_sound(Cue::WorkStart);
+                                }
+                            });
+                            ui.label(self.session_summary_text());
+                            self.recent_configs_ui(ui);
+                            if let Some(err) = &self.audio_error {
+                                ui.colored_label(egui::Color32::RED, format!("Sound problem: {}", err));
+                            }
+                            if let Some(err) = self.cue_decode_errors.iter().flatten().next() {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!("A cue sound couldn't be decoded, beeps play instead: {}", err),
+                                );
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Tag");
+                                if ui.add(egui::TextEdit::singleline(&mut self.settings.tag).desired_width(140.0)).changed() {
+                                    self.save_settings();
+                                }
+                                let tags = self.history.tags();
+                                if !tags.is_empty() {
+                                    ui.menu_button("Recent", |ui| {
+                                        for tag in tags {
+                                            if ui.button(&tag).clicked() {
+                                                self.settings.tag = tag;
+                                                self.save_settings();
+                                                ui.close_menu();
+                                            }
+                                        }
+                                    });
+                                }
+                            });
+                        }
+                        TimerState::LeadUp => {
+                            controls_row(ui, controls, |ui| {
+                                if ui.button("Pause").clicked() {
+                                    self.pause();
+                                }
+                                if ui.button("Stop").clicked() {
+                                    self.request_stop();
+                                }
+                            });
+                        }
+                        TimerState::Workout | TimerState::Rest => {
+                            controls_row(ui, controls, |ui| {
+                                if ui.button("Pause").clicked() {
+                                    self.pause();
+                                }
+                                // No right-click menu on a touchscreen
+                                if controls == Controls::Touch && !self.stopwatch() && !self.settings.manual_advance
+                                    && ui.button("Skip phase").clicked()
+                                {
+                                    self.skip_phase();
+                                }
+                                if ui.button("Stop").clicked() {
+                                    self.request_stop();
+                                }
+                                if self.state == TimerState::Rest
+                                    && ui.button(format!("+{}s rest", self.settings.rest_extension)).clicked()
+                                {
+                                    self.extend_rest();
+                                }
+                                if self.stopwatch() {
+                                    if ui.button("Lap").on_hover_text("Shortcut: L").clicked() {
+                                        self.lap();
+                                    }
+                                    if ui.button("Finish").clicked() {
+                                        self.skip_phase();
+                                    }
+                                } else if ui.button("+1 round").on_hover_text("Shortcut: +").clicked() {
+                                    self.add_round();
+                                }
+                                if self.settings.manual_advance && ui.button("Next").clicked() {
+                                    self.skip_phase();
+                                }
+                            });
+                        }
+                        TimerState::PausedLeadUp | TimerState::PausedWorkout | TimerState::PausedRest => {
+                            controls_row(ui, controls, |ui| {
+                                if ui.button("Resume").clicked() {
+                                    self.resume();
+                                }
+                                if ui.button("Stop").clicked() {
+                                    self.request_stop();
+                                }
+                                if self.state == TimerState::PausedRest
+                                    && ui.button(format!("+{}s rest", self.settings.rest_extension)).clicked()
+                                {
+                                    self.extend_rest();
+                                }
+                            });
+                        }
+                    }
+
+                    let block = match self.planned_round(self.current_round) {
+                        Some(round) if self.state != TimerState::Idle => format!(" ({})", round.name),
+                        _ => String::new(),
+                    };
+                    let rounds = if self.state == TimerState::Idle && self.settings.count_rounds_down {
+                        format::count(self.rounds_target() as u64, "round") // Nothing is left over before the start
+                    } else {
+                        self.round_label(self.rounds_target())
+                    };
+                    ui.label(format!("{}{}", rounds, block));
+                    if let Some(station) = self.station_label() {
+                        ui.label(station);
+                    }
+                    self.round_dots_ui(ui);
+                    let paused = if self.state.is_paused() { "Paused " } else { "" };
+                    ui.label(format!("State: {}{}", paused, self.phase_name()));
+                    if let Some(quote) = &self.rest_quote
+                        && matches!(self.state, TimerState::Rest | TimerState::PausedRest)
+                    {
+                        ui.label(egui::RichText::new(quote).small().italics());
+                    }
+                    if self.switch_flash_active() {
+                        ui.vertical_centered(|ui| {
+                            ui.label(egui::RichText::new("SWITCH").size(40.0).strong().color(self.phase_color()));
+                        });
+                    }
+
+                    // Add countdown timer
+                    if matches!(self.state, TimerState::LeadUp | TimerState::PausedLeadUp) {
+                        self.lead_up_ui(ui);
+                    } else if self.stopwatch() && self.state != TimerState::Idle {
+                        ui.horizontal(|ui| {
+                            ui.label("Elapsed:");
+                            let size = countdown_size;
+                            let elapsed = format::clock(self.stopwatch_elapsed().as_secs());
+                            ui.label(egui::RichText::new(elapsed).font(self.countdown_font(size)));
+                        });
+                    } else if self.overtime_secs() > 0 {
+                        let text = format!("Overtime: +{}", format::clock(self.overtime_secs()));
+                        ui.colored_label(ui.visuals().warn_fg_color, text);
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.label("Time remaining:");
+                            let size = countdown_size;
+                            ui.label(egui::RichText::new(format::clock(self.remaining_time)).font(self.countdown_font(size)));
+                        });
+                    }
+                    self.exercise_image_ui(ui);
+                    if self.state != TimerState::Idle && !self.stopwatch() {
+                        ui.label(format!("Session remaining: {}", format::clock(self.session_remaining_secs())));
+                    }
+                    if let Some(paused) = self.paused_secs() {
+                        ui.label(format!("Paused for {}", format::clock(paused)));
+                    }
+                    if self.state != TimerState::Idle && self.total_paused_secs() > 0 {
+                        ui.label(format!("Paused total: {}", format::clock(self.total_paused_secs())));
+                    }
+                    if self.state != TimerState::Idle && let Some(kcal) = self.session_kcal() {
+                        ui.label(format!("Calories: ~{:.0} kcal", kcal));
+                    }
+
+                    // Add progress bar
+                    let progress = match self.state {
+                        TimerState::Idle => 0.0,
+                        _ => phase_progress(self.remaining_time, self.phase_duration),
+                    };
+                    let progress = match self.settings.progress_direction {
+                        ProgressDirection::FillUp => progress,
+                        ProgressDirection::DrainDown if self.state == TimerState::Idle => 0.0,
+                        ProgressDirection::DrainDown => 1.0 - progress,
+                    };
+
+                    let progress_bar = egui::ProgressBar::new(progress)
+                    .show_percentage()
+                    .fill(self.phase_color());
+
+                    if !self.stopwatch() {
+                        if self.settings.session_progress && self.state != TimerState::Idle {
+                            self.session_progress_ui(ui);
+                        }
+                        ui.add(progress_bar);
+                    }
+
+                    egui::CollapsingHeader::new("History").show(ui, |ui| {
+                        self.history_ui(ui);
+                    });
+                }));
+            });
+        }
+
+        self.keyboard_help_ui(ctx);
+        self.command_palette_ui(ctx);
+        self.confirm_stop_ui(ctx);
+        self.confirm_start_ui(ctx);
+        self.confirm_paste_ui(ctx);
+        self.paint_focus_ring(ctx);
+        self.hide_idle_cursor(ctx);
+        self.second_display_ui(ctx);
+        self.drop_hover_ui(ctx);
+
+        if self.settings.battery_saver || dimmed || self.previewing() {
+            // Once per displayed second, which a preview goes through many times faster
+            ctx.request_repaint_after(self.time_to_next_tick());
+        } else if rest_pulse.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(33)); // Keep the pulse smooth
+        } else {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    // Runs every 30 seconds and once more before on_exit
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.remember_second_display_pos();
+        eframe::set_value(storage, UI_STATE_KEY, &self.ui);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.flush_settings();
+        if self.state != TimerState::Idle {
+            self.snapshot().save_to_file();
+        }
+    }
+}
+
+// How the sliders and the session buttons fit the window
+#[derive(Clone, Copy, PartialEq)]
+enum Controls {
+    Regular,
+    Narrow, // Shortened labels above the sliders, buttons wrap onto more rows
+    Touch,
+}
+
+// The label above a narrow slider, without the part in brackets: "Workout (sec)" becomes "Workout"
+fn slider_label(ui: &mut egui::Ui, label: &str, controls: Controls) {
+    if controls == Controls::Narrow {
+        ui.label(label.split(" (").next().unwrap_or(label));
+    }
+}
+
+// Logarithmic slider snapping to multiples of step, next to a box that still takes exact values.
+// The log scale keeps short intervals precise even with an hour long range.
+fn duration_slider(
+    ui: &mut egui::Ui,
+    value: &mut u64,
+    range: RangeInclusive<u64>,
+    step: u64,
+    label: &str,
+    controls: Controls,
+) -> bool {
+    let nudge = if step > 1 { step } else { NUDGE_SECS };
+    if controls == Controls::Touch {
+        return stepper(ui, value, range, nudge, label, true);
+    }
+    let (min, max) = (*range.start(), *range.end());
+    slider_label(ui, label, controls);
+    ui.horizontal(|ui| {
+        let before = *value;
+        // Snapping is relative to the range start, so start the slider on the grid
+        let response = ui.add(
+            egui::Slider::new(value, min / step * step..=max)
+                .logarithmic(true)
+                .step_by(step as f64)
+                .show_value(false),
+        );
+        let mut changed = response.changed();
+        changed |= nudge_slider(ui, &response, value, before, range.clone(), nudge);
+        changed |= validated_field(ui, label, value, range, true);
+        if controls != Controls::Narrow {
+            ui.label(label);
+        }
+        if changed {
+            *value = (*value).clamp(min, max);
+        }
+        changed
+    }).inner
+}
+
+// Linear slider with a typed field next to it, for the rounds and the lead-up
+fn number_slider(
+    ui: &mut egui::Ui,
+    value: &mut u64,
+    range: RangeInclusive<u64>,
+    label: &str,
+    duration: bool,
+    controls: Controls,
+) -> bool {
+    if controls == Controls::Touch {
+        return stepper(ui, value, range, 1, label, duration);
+    }
+    let nudge = if duration { NUDGE_SECS } else { 1 };
+    slider_label(ui, label, controls);
+    ui.horizontal(|ui| {
+        let before = *value;
+        let response = ui.add(egui::Slider::new(value, range.clone()).show_value(false));
+        let mut changed = response.changed();
+        changed |= nudge_slider(ui, &response, value, before, range.clone(), nudge);
+        changed |= validated_field(ui, label, value, range, duration);
+        if controls != Controls::Narrow {
+            ui.label(label);
+        }
+        changed
+    }).inner
+}
+
+// Step-wise slider input on top of dragging: the mouse wheel over the slider moves it one step per
+// notch, and a click on the track moves one step towards the click instead of jumping there.
+// `before` is the value from before the slider saw this frame's input, the slider has already
+// jumped by the time the click is complete, so the value at the press is kept until then.
+fn nudge_slider(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    value: &mut u64,
+    before: u64,
+    range: RangeInclusive<u64>,
+    step: u64,
+) -> bool {
+    let (min, max) = (*range.start(), *range.end());
+    let press_id = response.id.with("pressed_at");
+    let pressed_at = ui.data(|d| d.get_temp::<u64>(press_id));
+    if response.clicked() {
+        ui.data_mut(|d| d.remove::<u64>(press_id));
+        let from = pressed_at.unwrap_or(before);
+        *value = match (*value).cmp(&from) {
+            std::cmp::Ordering::Greater => (from + step).min(max),
+            std::cmp::Ordering::Less => from.saturating_sub(step).max(min),
+            std::cmp::Ordering::Equal => from,
+        };
+        return *value != before;
+    }
+    if !response.is_pointer_button_down_on() {
+        ui.data_mut(|d| d.remove::<u64>(press_id));
+    } else if pressed_at.is_none() {
+        ui.data_mut(|d| d.insert_temp(press_id, before));
+    }
+    if !response.hovered() {
+        return false;
+    }
+    // Taken from the input, so the panel under the slider doesn't scroll along
+    let scroll = ui.input_mut(|i| {
+        let delta = i.smooth_scroll_delta.y;
+        i.smooth_scroll_delta.y = 0.0;
+        delta
+    });
+    let id = response.id.with("wheel");
+    let total = ui.data(|d| d.get_temp::<f32>(id)).unwrap_or(0.0) + scroll;
+    let notches = (total / WHEEL_NOTCH).trunc();
+    ui.data_mut(|d| d.insert_temp(id, total - notches * WHEEL_NOTCH));
+    if notches == 0.0 {
+        return false;
+    }
+    let delta = notches as i64 * step as i64; // Scrolling up increases
+    let stepped = (*value as i64 + delta).clamp(min as i64, max as i64) as u64;
+    let changed = stepped != *value;
+    *value = stepped;
+    changed
+}
+
+// Touch mode's replacement for the sliders, – and + buttons around the typed field
+fn stepper(ui: &mut egui::Ui, value: &mut u64, range: RangeInclusive<u64>, step: u64, label: &str, duration: bool) -> bool {
+    let (min, max) = (*range.start(), *range.end());
+    ui.horizontal(|ui| {
+        let button_size = egui::vec2(TOUCH_BUTTON_HEIGHT, TOUCH_BUTTON_HEIGHT);
+        let mut changed = false;
+        if ui.add_enabled(*value > min, egui::Button::new("–").min_size(button_size)).clicked() {
+            *value = value.saturating_sub(step).max(min);
+            changed = true;
+        }
+        changed |= validated_field(ui, label, value, range, duration);
+        if ui.add_enabled(*value < max, egui::Button::new("+").min_size(button_size)).clicked() {
+            *value = (*value + step).min(max);
+            changed = true;
+        }
+        ui.label(label);
+        changed
+    }).inner
+}
+
+// The session buttons, stacked at full width and TOUCH_BUTTON_HEIGHT tall in touch mode
+fn controls_row<R>(ui: &mut egui::Ui, controls: Controls, add_contents: impl FnOnce(&mut egui::Ui) -> R) -> R {
+    match controls {
+        Controls::Regular => ui.horizontal(add_contents).inner,
+        Controls::Narrow => ui.horizontal_wrapped(add_contents).inner,
+        Controls::Touch => ui.with_layout(egui::Layout::top_down_justified(egui::Align::Center), |ui| {
+            ui.spacing_mut().interact_size.y = TOUCH_BUTTON_HEIGHT;
+            add_contents(ui)
+        }).inner,
+    }
+}
+
+// Text entry that only takes values within range. Anything else leaves the value as it was and
+// shows why under the field until it's corrected or the field loses focus.
+fn validated_field(ui: &mut egui::Ui, id: &str, value: &mut u64, range: RangeInclusive<u64>, duration: bool) -> bool {
+    let id = ui.make_persistent_id(("validated", id));
+    let shown = if duration { format::clock(*value) } else { value.to_string() };
+    let mut draft = ui.data(|d| d.get_temp::<String>(id)).unwrap_or_else(|| shown.clone());
+    let mut changed = false;
+    ui.vertical(|ui| {
+        let response = ui.add(egui::TextEdit::singleline(&mut draft).desired_width(56.0));
+        if !response.has_focus() {
+            ui.data_mut(|d| d.remove::<String>(id));
+            return;
+        }
+        ui.data_mut(|d| d.insert_temp(id, draft.clone()));
+        match format::validate_input(&draft, *range.start(), *range.end(), duration) {
+            Ok(parsed) => {
+                changed = parsed != *value;
+                *value = parsed;
+            }
+            Err(message) => {
+                ui.label(egui::RichText::new(message).small().color(ui.visuals().error_fg_color));
+            }
+        }
+    });
+    changed
+}
+
+// Rest for a work:rest ratio, rounded to whole seconds and kept within the slider range.
+// Also tells whether the range had to cut it.
+fn ratio_rest(work: u64, [work_part, rest_part]: [u64; 2], range: RangeInclusive<u64>) -> (u64, bool) {
+    let rest = (work * rest_part + work_part / 2) / work_part.max(1);
+    let clamped = rest.clamp(*range.start(), *range.end());
+    (clamped, clamped != rest)
+}
+
+// Scaled down to max_size on the longer side
+fn load_texture(ctx: &egui::Context, name: &str, path: &str, max_size: u32) -> Result<egui::TextureHandle, String> {
+    let image = image::open(path).map_err(|err| err.to_string())?;
+    let image = if image.width().max(image.height()) > max_size { image.thumbnail(max_size, max_size) } else { image };
+    let rgba = image.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    let image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice());
+    Ok(ctx.load_texture(name, image, egui::TextureOptions::LINEAR))
+}
+
+// The fanfare star, decoded once at startup. If that fails the fanfare goes without stars.
+fn load_star(ctx: &egui::Context) -> Option<egui::TextureHandle> {
+    let image = image::load_from_memory(FANFARE_STAR)
+        .map_err(|err| log::error!("Failed to decode the fanfare star: {}", err))
+        .ok()?;
+    let rgba = image.to_rgba8();
+    let size = [rgba.width() as usize, rgba.height() as usize];
+    let image = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_flat_samples().as_slice());
+    Some(ctx.load_texture("star", image, egui::TextureOptions::default()))
+}
+
+// Crops the image in the middle so it fills the area without being stretched
+fn cover_uv(image: egui::Vec2, area: egui::Vec2) -> egui::Rect {
+    let image_aspect = image.x / image.y.max(1.0);
+    let area_aspect = area.x / area.y.max(1.0);
+    let size = if image_aspect > area_aspect {
+        egui::vec2(area_aspect / image_aspect, 1.0)
+    } else {
+        egui::vec2(1.0, image_aspect / area_aspect)
+    };
+    egui::Rect::from_center_size(egui::pos2(0.5, 0.5), size)
+}
+
+// Path entry that only takes effect on Enter or focus loss, so half-typed paths never get used
+fn path_field(ui: &mut egui::Ui, label: &str, value: &mut String, hint: &str) -> bool {
+    let id = ui.make_persistent_id(label);
+    let mut draft = ui.data(|d| d.get_temp::<String>(id)).unwrap_or_else(|| value.clone());
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let response = ui.text_edit_singleline(&mut draft).on_hover_text(hint);
+        if response.has_focus() {
+            ui.data_mut(|d| d.insert_temp(id, draft.clone()));
+        } else {
+            ui.data_mut(|d| d.remove::<String>(id));
+            if response.lost_focus() && draft != *value {
+                *value = draft.clone();
+                changed = true;
+            }
+        }
+    });
+    changed
+}
+
+// Adds the countdown font as its own family. The rest of the UI keeps the defaults, with a
+// fallback font behind them for scripts they don't cover. Returns why that font isn't used.
+fn install_fonts(ctx: &egui::Context, fallback_font: &str) -> Option<String> {
+    let mut fonts = egui::FontDefinitions::default();
+    let fallback = fonts::load_fallback(fallback_font);
+    if let Ok(Some(data)) = &fallback {
+        fonts.font_data.insert(FALLBACK_FONT.to_string(), std::sync::Arc::new(egui::FontData::from_owned(data.clone())));
+        for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+            fonts.families.entry(family).or_default().push(FALLBACK_FONT.to_string());
+        }
+    }
+    fonts.font_data.insert(
+        COUNTDOWN_FAMILY.to_string(),
+        std::sync::Arc::new(egui::FontData::from_static(COUNTDOWN_FONT)),
+    );
+    fonts
+        .families
+        .insert(egui::FontFamily::Name(COUNTDOWN_FAMILY.into()), vec![COUNTDOWN_FAMILY.to_string()]);
+    ctx.set_fonts(fonts);
+    fallback.err().inspect(|err| log::warn!("Fallback font not loaded: {}", err))
+}
+
+// Set once at startup, for the dark and the light theme, so following the system theme keeps
+// the same sizes
+fn apply_style(ctx: &egui::Context, touch: bool) {
+    ctx.all_styles_mut(|style| {
+        // Define custom text styles
+        style.text_styles = [
+            (egui::TextStyle::Heading, egui::FontId::new(24.0, egui::FontFamily::Proportional)),
+            (egui::TextStyle::Body, egui::FontId::new(18.0, egui::FontFamily::Proportional)),
+            (egui::TextStyle::Button, egui::FontId::new(30.0, egui::FontFamily::Proportional)),
+        ]
+        .into();
+
+        // Adjust sizes for sliders and progress bars
+        style.spacing.slider_width = 240.0; // Increase slider width
+        style.spacing.item_spacing.y = 10.0; // Increase vertical spacing between items
+        style.spacing.interact_size.y = 30.0; // Increase height of interactive elements (including sliders)
+
+        // Room for a finger, the session buttons get taller still in controls_row
+        if touch {
+            style.spacing.item_spacing = egui::vec2(12.0, 14.0);
+            style.spacing.button_padding = egui::vec2(16.0, 8.0);
+            style.spacing.interact_size.y = 48.0;
+            style.spacing.icon_width = 28.0;
+            style.spacing.icon_width_inner = 16.0;
+            style.spacing.icon_spacing = 8.0;
+        }
+    });
+}
+
+fn main() -> eframe::Result<()> {
+    crash::install_hook();
+    logging::init(std::env::args().any(|arg| arg == "--verbose"));
+    log::info!("Starting {} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    url_scheme::register();
+    let link = url_scheme::find_in_args();
+
+    // Hand over to an already running timer unless asked for another one
+    let message = link.as_ref().map_or_else(|| "focus".to_string(), |link| format!("open:{}", link));
+    let instance = if std::env::args().any(|arg| arg == "--allow-multiple") {
+        None
+    } else {
+        match instance::claim(&message) {
+            Startup::Primary(guard) => Some(guard),
+            Startup::AlreadyRunning => {
+                log::info!("Already running, focused the existing window");
+                return Ok(());
+            }
+            Startup::Unguarded => None,
+        }
+    };
+
+    let options = eframe::NativeOptions {
+        // Use the window_builder hook to set the initial window size
+        window_builder: Some(Box::new(|builder| {
+            builder
+                .with_title("Workout Timer") // Set the window title
+                .with_inner_size((450.0, 450.0)) // Set the initial window size
+                .with_min_inner_size(MIN_WINDOW_SIZE) // Smaller than this nothing fits, however it's laid out
+        })),
+        // Next to the settings, so portable mode keeps it too. Without a writable folder the state
+        // only lives for this run.
+        persistence_path: Some(paths::data_file(UI_STATE_FILE)),
+        ..Default::default()
+    };
+
+    eframe::run_native(
         "Workout Timer",
         options,
-        Box::new(|_cc| Ok(Box::new(WorkoutTimer::new()))),
+        Box::new(|cc| {
+            let mut timer = WorkoutTimer::new();
+            timer.font_error = install_fonts(&cc.egui_ctx, &timer.settings.fallback_font);
+            timer.star_texture = load_star(&cc.egui_ctx);
+            timer.touch_style = timer.settings.touch_mode;
+            apply_style(&cc.egui_ctx, timer.touch_style);
+            if let Some(ui) = cc.storage.and_then(|storage| eframe::get_value(storage, UI_STATE_KEY)) {
+                timer.ui = ui;
+            }
+            // Before the first frame, so the window never shows the wrong theme
+            cc.egui_ctx.set_theme(timer.settings.theme.preference());
+            if let Some(guard) = &instance {
+                guard.set_context(cc.egui_ctx.clone());
+            }
+            timer.instance = instance;
+            if let Some(link) = link {
+                timer.open_link(&link);
+            }
+            Ok(Box::new(timer))
+        }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use audio::MockOutput;
+
+    #[test]
+    fn phase_progress_of_a_zero_length_phase_is_complete() {
+        assert_eq!(phase_progress(0, 0), 1.0);
+        assert_eq!(phase_progress(5, 0), 1.0);
+        assert!(!phase_progress(0, 0).is_nan());
+    }
+
+    #[test]
+    fn phase_progress_stays_within_the_phase() {
+        assert_eq!(phase_progress(60, 60), 0.0);
+        assert_eq!(phase_progress(15, 60), 0.75);
+        assert_eq!(phase_progress(0, 60), 1.0); // Overtime, past the planned end
+        assert_eq!(phase_progress(90, 60), 0.0); // More left than the phase, after it was shortened
+    }
+
+    fn defaults_json() -> String {
+        serde_json::to_string_pretty(&Settings::default()).unwrap()
+    }
+
+    fn corrupt_backups(dir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with("settings.json.corrupt-"))
+            .collect()
+    }
+
+    // The unreadable file is kept as it was, the defaults are saved in its place and in use
+    fn assert_set_aside(contents: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(&path, contents).unwrap();
+
+        let (settings, notice) = Settings::load_from_file(&path);
+        assert_eq!(serde_json::to_string_pretty(&settings).unwrap(), defaults_json());
+        let backups = corrupt_backups(dir.path());
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), contents);
+        assert!(notice.unwrap().contains(&backups[0].display().to_string()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), defaults_json());
+    }
+
+    #[test]
+    fn truncated_settings_are_set_aside() {
+        let settings = Settings { rounds: 12, ..Settings::default() };
+        let json = serde_json::to_string_pretty(&settings).unwrap();
+        assert_set_aside(&json[..json.len() / 2]);
+    }
+
+    #[test]
+    fn invalid_settings_are_set_aside() {
+        assert_set_aside("{ \"rounds\": \"twelve\" }");
+        assert_set_aside("not json at all");
+    }
+
+    #[test]
+    fn missing_settings_file_gets_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        let (_, notice) = Settings::load_from_file(&path);
+        assert!(notice.is_none());
+        assert_eq!(fs::read_to_string(&path).unwrap(), defaults_json());
+        assert!(corrupt_backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn readable_settings_load_without_a_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILE);
+        fs::write(&path, "{ \"rounds\": 12 }").unwrap();
+        let (settings, notice) = Settings::load_from_file(&path);
+        assert_eq!(settings.rounds, 12);
+        assert!(notice.is_none());
+        assert!(corrupt_backups(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn settings_that_cant_be_set_aside_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join(SETTINGS_FILE);
+        let notice = Settings::set_aside_corrupt(&path);
+        assert!(notice.contains("couldn't be backed up"), "{}", notice);
+        assert!(!path.exists());
+    }
+
+    // A timer that keeps its settings and history in the given dir and never reaches the sound card
+    fn timer_in(dir: &Path) -> WorkoutTimer {
+        let path = dir.join(SETTINGS_FILE);
+        let (settings, _) = Settings::load_from_file(&path);
+        let mut timer = WorkoutTimer::with_settings(settings, path);
+        timer.history_path = dir.join(HISTORY_FILE);
+        timer.audio = AudioOut::new(Box::new(MockOutput::default()));
+        timer
+    }
+
+    // Lets the running phase run out, as if its time had passed
+    fn finish_phase(timer: &mut WorkoutTimer) {
+        let phase = timer.real_time(Duration::from_secs(timer.phase_duration));
+        timer.start_time = Some(Instant::now() - phase);
+        timer.update();
+    }
+
+    #[test]
+    fn unsaved_settings_are_written_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        timer.settings.rounds = 12; // Changed without going through save_settings
+        eframe::App::on_exit(&mut timer, None);
+        assert_eq!(Settings::load_from_file(&timer.settings_path).0.rounds, 12);
+    }
+
+    #[test]
+    fn settings_clamped_on_load_are_written_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(SETTINGS_FILE), "{ \"rounds\": 500 }").unwrap();
+        let mut timer = timer_in(dir.path());
+        eframe::App::on_exit(&mut timer, None);
+        let saved: serde_json::Value = serde_json::from_str(&fs::read_to_string(&timer.settings_path).unwrap()).unwrap();
+        assert_eq!(saved["rounds"], 50);
+    }
+
+    #[test]
+    fn outside_edits_are_kept_on_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        timer.settings.rounds = 12;
+        fs::write(&timer.settings_path, "{ \"rounds\": 7 }").unwrap();
+        let file = fs::File::options().write(true).open(&timer.settings_path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap(); // Not picked up yet
+        eframe::App::on_exit(&mut timer, None);
+        assert_eq!(fs::read_to_string(&timer.settings_path).unwrap(), "{ \"rounds\": 7 }");
+    }
+
+    // Sample counts of the sounds queued for each cue, one entry per cue
+    fn queued_lengths(mock: &MockOutput) -> Vec<usize> {
+        mock.sounds.borrow_mut().drain(..).map(|(_, sound)| sound.count()).collect()
+    }
+
+    #[test]
+    fn cues_that_dont_decode_beep_instead() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        let mock = MockOutput { undecodable: true, ..Default::default() };
+        timer.audio = AudioOut::new(Box::new(mock.clone()));
+        timer.settings.repeat_complete_until_dismissed = false;
+
+        let one_beep = sounds::fallback_sound(Cue::WorkStart).count();
+        for cue in [Cue::WorkStart, Cue::WorkEnd, Cue::Complete] {
+            timer.play_sound(cue);
+            assert_eq!(queued_lengths(&mock), vec![sounds::fallback_sound(cue).count()]);
+            assert_eq!(sounds::fallback_sound(cue).count(), one_beep * cue.fallback_beeps());
+            assert_eq!(timer.cue_decode_errors[cue.slot()].as_deref(), Some("Unrecognized format"));
+        }
+        assert!(timer.audio_error.is_none()); // The output works, only decoding doesn't
+    }
+
+    #[test]
+    fn a_cue_that_decodes_again_clears_its_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        let mock = MockOutput::default();
+        timer.audio = AudioOut::new(Box::new(mock.clone()));
+        timer.cue_decode_errors[Cue::WorkEnd.slot()] = Some("Unrecognized format".to_string());
+        timer.play_sound(Cue::WorkEnd);
+        assert!(timer.cue_decode_errors[Cue::WorkEnd.slot()].is_none());
+        assert_eq!(mock.sounds.borrow().len(), 1);
+    }
+
+    #[test]
+    fn a_single_round_session_completes_without_a_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        timer.settings.rounds = 1;
+        timer.settings.lead_up_duration = 0;
+        timer.settings.workout_duration = 20;
+        timer.settings.rest_duration = 10;
+
+        timer.start();
+        timer.update();
+        assert_eq!(timer.state, TimerState::Workout);
+        assert_eq!(timer.session_remaining_secs(), 20);
+
+        finish_phase(&mut timer);
+        assert_eq!(timer.state, TimerState::Idle);
+        assert!(timer.fanfare_start_time.is_some());
+        let record = timer.last_session.as_ref().unwrap();
+        assert_eq!((record.rounds, record.work_secs, record.rest_secs), (1, 20, 0));
+    }
+
+    #[test]
+    fn start_clears_the_previous_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_in(dir.path());
+        timer.settings.rounds = 1;
+        timer.settings.lead_up_duration = 0;
+        timer.settings.repeat_complete_until_dismissed = true;
+        timer.start();
+        timer.update();
+        finish_phase(&mut timer);
+        assert!(timer.fanfare_start_time.is_some());
+        assert!(timer.completion_alarm);
+        assert!(timer.last_session.is_some());
+
+        timer.start();
+        assert!(timer.fanfare_start_time.is_none());
+        assert!(!timer.completion_alarm);
+        assert!(timer.last_session.is_none());
+        assert_eq!(timer.state, TimerState::LeadUp);
+    }
+
+    fn timer_with(dir: &Path, rounds: u32, lead_up: u64) -> WorkoutTimer {
+        let mut timer = timer_in(dir);
+        timer.settings.rounds = rounds;
+        timer.settings.lead_up_duration = lead_up;
+        timer.settings.workout_duration = 20;
+        timer.settings.rest_duration = 10;
+        timer
+    }
+
+    // Puts the running phase secs into its own clock
+    fn run_phase_for(timer: &mut WorkoutTimer, secs: u64) {
+        timer.start_time = Some(Instant::now() - timer.real_time(Duration::from_secs(secs)));
+        timer.update();
+    }
+
+    #[test]
+    fn a_restarted_phase_counts_the_time_before_the_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_with(dir.path(), 2, 0);
+        timer.start();
+        timer.update();
+        run_phase_for(&mut timer, 15);
+        timer.restart_phase();
+        assert_eq!(timer.remaining_time, 20);
+        finish_phase(&mut timer);
+        assert_eq!(timer.state, TimerState::Rest);
+        assert_eq!(timer.session_work_secs, 35);
+
+        run_phase_for(&mut timer, 4);
+        timer.pause();
+        timer.restart_phase();
+        timer.resume();
+        finish_phase(&mut timer);
+        assert_eq!(timer.session_rest_secs, 14);
+        finish_phase(&mut timer);
+        finish_phase(&mut timer);
+        let record = timer.last_session.as_ref().unwrap();
+        assert_eq!((record.work_secs, record.rest_secs), (55, 24));
+    }
+
+    #[test]
+    fn a_resumed_snapshot_carries_on_with_the_session_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_with(dir.path(), 2, 0);
+        timer.start();
+        timer.session_started_at = Some(Instant::now() - Duration::from_secs(45));
+        timer.update();
+        finish_phase(&mut timer);
+        timer.pause();
+        timer.session_paused_secs = 7;
+        let json = serde_json::to_string(&timer.snapshot()).unwrap();
+
+        let mut restored = timer_with(dir.path(), 2, 0);
+        restored.restore_snapshot(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.state, TimerState::PausedRest);
+        assert_eq!((restored.session_work_secs, restored.session_rest_secs), (20, 0));
+        assert_eq!((restored.session_pauses, restored.session_paused_secs), (1, 7));
+        assert_eq!(restored.session_started_at.unwrap().elapsed().as_secs(), 45);
+
+        restored.resume();
+        finish_phase(&mut restored);
+        finish_phase(&mut restored);
+        finish_phase(&mut restored);
+        let record = restored.last_session.as_ref().unwrap();
+        assert_eq!((record.work_secs, record.rest_secs, record.pauses), (40, 20, 1));
+        assert!(record.total_secs >= 45);
+    }
+
+    #[test]
+    fn snapshots_from_older_versions_still_load() {
+        let json = r#"{ "state": "PausedWorkout", "current_round": 2, "rounds": 5, "phase_duration": 30, "remaining_time": 12, "saved_at": 0 }"#;
+        let snapshot: SessionSnapshot = serde_json::from_str(json).unwrap();
+        assert_eq!((snapshot.current_round, snapshot.elapsed_secs, snapshot.work_secs), (2, 0, 0));
+    }
+
+    fn circuit_timer(dir: &Path) -> WorkoutTimer {
+        let mut timer = timer_with(dir, 4, 0);
+        timer.settings.use_stations = true;
+        timer.settings.stations = vec!["Rower".to_string(), "Bike".to_string()];
+        timer.settings.laps = 3;
+        timer
+    }
+
+    #[test]
+    fn a_shared_circuit_is_applied_with_its_stations() {
+        let dir = tempfile::tempdir().unwrap();
+        let circuit = circuit_timer(dir.path()).shared_config();
+        let mut timer = timer_with(dir.path(), 4, 0);
+        let plain = timer.shared_config();
+        timer.apply_shared_config(circuit);
+        assert_eq!(timer.planned_rounds(), 6);
+
+        timer.apply_shared_config(plain);
+        assert_eq!(timer.planned_rounds(), 4);
+        assert_eq!(timer.settings.stations.len(), 2); // Kept for when stations are turned on again
+    }
+
+    #[test]
+    fn a_link_turns_the_stations_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = circuit_timer(dir.path());
+        timer.open_link("interval-timer://open?rounds=5");
+        assert!(!timer.settings.use_stations);
+        assert_eq!(timer.planned_rounds(), 5);
+    }
+
+    #[test]
+    fn a_link_switches_to_intervals() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_with(dir.path(), 4, 0);
+        timer.settings.mode = TimerMode::Stopwatch;
+        timer.open_link("interval-timer://open?work=40&rounds=8");
+        assert_eq!(timer.settings.mode, TimerMode::Intervals);
+        assert_eq!(timer.planned_rounds(), 8);
+    }
+
+    #[test]
+    fn adjusted_phases_count_the_time_actually_spent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_with(dir.path(), 2, 0);
+        timer.start();
+        timer.update();
+        run_phase_for(&mut timer, 5);
+        timer.pause();
+        timer.adjust_remaining(-10);
+        assert_eq!(timer.remaining_time, 5);
+        timer.resume();
+        finish_phase(&mut timer); // The 5 seconds left
+        assert_eq!(timer.state, TimerState::Rest);
+        assert_eq!(timer.session_work_secs, 10);
+
+        run_phase_for(&mut timer, 5);
+        timer.pause();
+        timer.adjust_remaining(30);
+        assert_eq!(timer.remaining_time, 35);
+        timer.resume();
+        finish_phase(&mut timer);
+        assert_eq!(timer.session_rest_secs, 40);
+    }
+
+    #[test]
+    fn a_single_round_has_no_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let timer = timer_with(dir.path(), 1, 0);
+        assert_eq!(timer.rounds_secs(0, 1), 20);
+        assert_eq!(timer.session_total_secs(), 20);
+    }
+
+    #[test]
+    fn every_round_of_a_longer_session_has_its_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let timer = timer_with(dir.path(), 3, 0);
+        assert_eq!(timer.rounds_secs(0, 3), 90);
+        assert_eq!(timer.rounds_secs(1, 3), 60);
+        assert_eq!(timer.rounds_secs(3, 3), 0);
+        assert_eq!(timer.session_total_secs(), 90);
+    }
+
+    #[test]
+    fn the_lead_up_counts_towards_the_session() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(timer_with(dir.path(), 1, 5).session_total_secs(), 25);
+        assert_eq!(timer_with(dir.path(), 3, 5).session_total_secs(), 95);
+    }
+
+    #[test]
+    fn pomodoro_sessions_count_their_long_breaks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut timer = timer_with(dir.path(), 4, 0);
+        timer.settings.mode = TimerMode::Pomodoro;
+        timer.settings.long_break_every = 2;
+        timer.settings.long_break_duration = 60;
+        assert_eq!(timer.session_total_secs(), 4 * 20 + 10 + 60 + 10 + 60);
+    }
+}
@@ -0,0 +1,168 @@
+#[cfg(feature = "mqtt")]
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+#[cfg(feature = "mqtt")]
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+#[cfg(feature = "mqtt")]
+const QUEUE_SIZE: usize = 16;
+const DEFAULT_PORT: u16 = 1883;
+
+#[derive(Clone, PartialEq)]
+pub struct MqttConfig {
+    pub broker: String, // mqtt://host:port, or just host
+    pub username: String,
+    pub password: String,
+    pub topic_prefix: String,
+}
+
+#[derive(Clone, PartialEq)]
+#[cfg_attr(not(feature = "mqtt"), allow(dead_code))] // Set by the connection thread
+pub enum LinkState {
+    Connecting,
+    Connected,
+    Failed(String), // Retried every few seconds
+}
+
+#[cfg_attr(not(feature = "mqtt"), allow(dead_code))] // Only the client publishes
+struct Link {
+    config: MqttConfig,
+    state: Arc<Mutex<LinkState>>,
+    #[cfg(feature = "mqtt")]
+    client: Option<Client>, // None when the broker address is unusable
+}
+
+// Publishes the timer state, retained, to <prefix>/state on every phase or round change and as a
+// heartbeat in between. Connecting, reconnecting and sending all happen on a background thread,
+// so an unreachable broker never holds up a frame.
+#[derive(Default)]
+pub struct MqttPublisher {
+    link: Option<Link>,
+    last_change: Option<String>, // Phase and round of the last message
+    last_sent: Option<Instant>,
+}
+
+impl MqttPublisher {
+    pub fn available() -> bool {
+        cfg!(feature = "mqtt")
+    }
+
+    // None when publishing is off, which also disconnects
+    pub fn state(&self) -> Option<LinkState> {
+        let link = self.link.as_ref()?;
+        Some(link.state.lock().map(|state| state.clone()).unwrap_or(LinkState::Connecting))
+    }
+
+    pub fn update(&mut self, config: Option<MqttConfig>, change: String, payload: String) {
+        let Some(config) = config.filter(|_| Self::available()) else {
+            self.link = None;
+            self.last_change = None;
+            return;
+        };
+        if self.link.as_ref().is_none_or(|link| link.config != config) {
+            self.link = Some(Link::open(config));
+            self.last_change = None;
+        }
+        let heartbeat = self.last_sent.is_none_or(|t| t.elapsed() >= HEARTBEAT_INTERVAL);
+        if self.last_change.as_ref() != Some(&change) || heartbeat {
+            if let Some(link) = &self.link {
+                link.publish(payload);
+            }
+            self.last_change = Some(change);
+            self.last_sent = Some(Instant::now());
+        }
+    }
+}
+
+impl Link {
+    #[cfg(feature = "mqtt")]
+    fn open(config: MqttConfig) -> Self {
+        let state = Arc::new(Mutex::new(LinkState::Connecting));
+        let (host, port) = match parse_broker(&config.broker) {
+            Ok(address) => address,
+            Err(err) => {
+                *state.lock().unwrap() = LinkState::Failed(err);
+                return Self { config, state, client: None };
+            }
+        };
+        let mut options = MqttOptions::new(format!("interval_timer-{}", std::process::id()), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if !config.username.is_empty() {
+            options.set_credentials(config.username.as_str(), config.password.as_str());
+        }
+        let (client, mut connection) = Client::new(options, QUEUE_SIZE);
+        let thread_state = state.clone();
+        // Ends once the client is dropped, the eventloop reconnects on its own until then
+        std::thread::spawn(move || {
+            for event in connection.iter() {
+                let next = match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => LinkState::Connected,
+                    Ok(_) => continue,
+                    Err(err) => LinkState::Failed(err.to_string()),
+                };
+                let failed = matches!(next, LinkState::Failed(_));
+                if let Ok(mut state) = thread_state.lock()
+                    && *state != next
+                {
+                    match &next {
+                        LinkState::Failed(err) => log::warn!("MQTT broker unavailable: {}", err),
+                        _ => log::info!("Connected to the MQTT broker"),
+                    }
+                    *state = next;
+                }
+                if failed {
+                    std::thread::sleep(RETRY_DELAY);
+                }
+            }
+        });
+        Self { config, state, client: Some(client) }
+    }
+
+    // Never opened without the feature, MqttPublisher::update checks available() first
+    #[cfg(not(feature = "mqtt"))]
+    fn open(config: MqttConfig) -> Self {
+        Self { config, state: Arc::new(Mutex::new(LinkState::Connecting)) }
+    }
+
+    #[cfg(feature = "mqtt")]
+    fn publish(&self, payload: String) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        let topic = format!("{}/state", self.config.topic_prefix.trim_end_matches('/'));
+        // A full queue means the broker is away, the next heartbeat carries the state anyway
+        let _ = client.try_publish(topic, QoS::AtLeastOnce, true, payload);
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    fn publish(&self, _payload: String) {}
+}
+
+#[cfg(feature = "mqtt")]
+impl Drop for Link {
+    fn drop(&mut self) {
+        if let Some(client) = &self.client {
+            let _ = client.try_disconnect();
+        }
+    }
+}
+
+// "mqtt://broker.lan:1883", "tcp://broker.lan" or "broker.lan", the port defaults to 1883
+pub fn parse_broker(broker: &str) -> Result<(String, u16), String> {
+    let broker = broker.trim();
+    if broker.starts_with("mqtts://") || broker.starts_with("ssl://") {
+        return Err("TLS brokers aren't supported, use mqtt://".to_string());
+    }
+    let address = broker.strip_prefix("mqtt://").or_else(|| broker.strip_prefix("tcp://")).unwrap_or(broker);
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| format!("Invalid port \"{}\"", port))?),
+        None => (address, DEFAULT_PORT),
+    };
+    if host.is_empty() {
+        return Err("No broker address set".to_string());
+    }
+    Ok((host.to_string(), port))
+}
@@ -0,0 +1,34 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Keeps a text file in sync with the timer for streaming overlays (e.g. an OBS text source)
+#[derive(Default)]
+pub struct OverlayWriter {
+    last_write: Option<Instant>,
+    last_contents: Option<String>,
+}
+
+impl OverlayWriter {
+    // Writes at most once per second, and only when the contents changed
+    pub fn update(&mut self, path: &str, contents: &str) {
+        if path.is_empty() || self.last_contents.as_deref() == Some(contents) {
+            return;
+        }
+        if contents.is_empty() || self.last_write.is_none_or(|t| t.elapsed() >= WRITE_INTERVAL) {
+            // Disk errors are ignored on purpose, the overlay must never disturb the timer
+            let _ = write_atomic(Path::new(path), contents);
+            self.last_write = Some(Instant::now());
+            self.last_contents = Some(contents.to_string());
+        }
+    }
+}
+
+// Write to a temporary file first so readers never see a half-written file
+fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PORTABLE_MARKER: &str = "portable.txt";
+const APP_DIR_NAME: &str = "interval_timer";
+
+// Files older versions wrote to the working directory
+const LEGACY_FILES: &[&str] = &["settings.json", "session.json"];
+
+lazy_static::lazy_static! {
+    static ref DATA_DIR: PathBuf = init_data_dir();
+}
+
+pub fn data_file(name: &str) -> PathBuf {
+    DATA_DIR.join(name)
+}
+
+#[cfg(not(test))]
+fn init_data_dir() -> PathBuf {
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let portable_flag = env::args().any(|arg| arg == "--portable");
+    let dir = resolve_data_dir(exe_dir.as_deref(), portable_flag, dirs::config_dir().as_deref());
+    let _ = fs::create_dir_all(&dir);
+
+    if let Ok(cwd) = env::current_dir() {
+        migrate_legacy_files(&cwd, &dir);
+    }
+    dir
+}
+
+// Tests never touch the real data dir
+#[cfg(test)]
+fn init_data_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("interval_timer-test-{}", std::process::id()));
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+// Portable mode keeps everything next to the executable, otherwise the platform config dir is used
+fn resolve_data_dir(exe_dir: Option<&Path>, portable_flag: bool, config_dir: Option<&Path>) -> PathBuf {
+    if let Some(exe_dir) = exe_dir
+        && (portable_flag || exe_dir.join(PORTABLE_MARKER).exists())
+    {
+        return exe_dir.to_path_buf();
+    }
+    match (config_dir, exe_dir) {
+        (Some(config_dir), _) => config_dir.join(APP_DIR_NAME),
+        (None, Some(exe_dir)) => exe_dir.to_path_buf(),
+        (None, None) => PathBuf::from("."),
+    }
+}
+
+// Carry files over from the old location, files already in the data dir always win
+fn migrate_legacy_files(legacy_dir: &Path, data_dir: &Path) {
+    for name in LEGACY_FILES {
+        let legacy = legacy_dir.join(name);
+        let target = data_dir.join(name);
+        if legacy.is_file() && !target.exists() {
+            let _ = fs::copy(&legacy, &target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portable_flag_keeps_data_next_to_the_exe() {
+        let exe = tempfile::tempdir().unwrap();
+        let config = tempfile::tempdir().unwrap();
+        let dir = resolve_data_dir(Some(exe.path()), true, Some(config.path()));
+        assert_eq!(dir, exe.path());
+    }
+
+    #[test]
+    fn portable_marker_keeps_data_next_to_the_exe() {
+        let exe = tempfile::tempdir().unwrap();
+        let config = tempfile::tempdir().unwrap();
+        fs::write(exe.path().join(PORTABLE_MARKER), "").unwrap();
+        let dir = resolve_data_dir(Some(exe.path()), false, Some(config.path()));
+        assert_eq!(dir, exe.path());
+    }
+
+    #[test]
+    fn installed_builds_use_the_config_dir() {
+        let exe = tempfile::tempdir().unwrap();
+        let config = tempfile::tempdir().unwrap();
+        let dir = resolve_data_dir(Some(exe.path()), false, Some(config.path()));
+        assert_eq!(dir, config.path().join(APP_DIR_NAME));
+        assert_eq!(resolve_data_dir(Some(exe.path()), false, None), exe.path());
+        assert_eq!(resolve_data_dir(None, true, None), PathBuf::from("."));
+    }
+
+    #[test]
+    fn migration_copies_missing_files_and_never_overwrites() {
+        let legacy = tempfile::tempdir().unwrap();
+        let data = tempfile::tempdir().unwrap();
+        fs::write(legacy.path().join("settings.json"), "old settings").unwrap();
+        fs::write(legacy.path().join("session.json"), "old session").unwrap();
+        fs::write(data.path().join("settings.json"), "new settings").unwrap();
+
+        migrate_legacy_files(legacy.path(), data.path());
+        assert_eq!(fs::read_to_string(data.path().join("settings.json")).unwrap(), "new settings");
+        assert_eq!(fs::read_to_string(data.path().join("session.json")).unwrap(), "old session");
+        assert!(legacy.path().join("settings.json").exists());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+const MAX_INCLUDE_DEPTH: usize = 8; // Also stops include cycles
+
+// Hand-written workout plans, e.g.
+//
+//   include = ["warmups.toml"]
+//
+//   [[block]]
+//   use = "Warmup"
+//
+//   [[block]]
+//   name = "Sprints"
+//   work = 30
+//   rest = 15
+//   repeat = 6
+//   image = "images/sprint.png"
+//
+// Blocks from included files are only definitions for `use`, they don't run by themselves.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanFile {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    block: Vec<BlockSpec>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockSpec {
+    name: Option<String>,
+    work: Option<u64>,
+    rest: Option<u64>,
+    repeat: Option<u32>,
+    image: Option<String>, // Relative to the file the block is written in
+    #[serde(rename = "use")]
+    use_block: Option<String>,
+}
+
+// One round of a plan, also the rows of the round list editor in the settings
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanRound {
+    pub name: String,
+    pub work: u64,
+    pub rest: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>, // Shown during the work phase
+}
+
+pub struct Plan {
+    pub blocks: usize,
+    pub rounds: Vec<PlanRound>,
+}
+
+impl Plan {
+    pub fn load(path: &Path) -> Result<Plan, String> {
+        let file = read_plan_file(path)?;
+        let mut definitions = HashMap::new();
+        collect_definitions(path, &file, &mut definitions, 0)?;
+
+        let mut rounds = Vec::new();
+        for (index, spec) in file.block.iter().enumerate() {
+            let context = format!("{}: block {}", path.display(), index + 1);
+            let spec = match &spec.use_block {
+                Some(name) => {
+                    let template = definitions
+                        .get(name)
+                        .ok_or_else(|| format!("{}: `use` refers to unknown block \"{}\"", context, name))?;
+                    // Fields set next to `use` override the named block
+                    BlockSpec {
+                        name: spec.name.clone().or(template.name.clone()),
+                        work: spec.work.or(template.work),
+                        rest: spec.rest.or(template.rest),
+                        repeat: spec.repeat.or(template.repeat),
+                        image: resolve_image(path, &spec.image).or(template.image.clone()),
+                        use_block: None,
+                    }
+                }
+                None => BlockSpec { image: resolve_image(path, &spec.image), ..spec.clone() },
+            };
+            let name = spec.name.clone().unwrap_or_else(|| format!("Block {}", index + 1));
+            let context = format!("{} (\"{}\")", context, name);
+            let work = match spec.work {
+                Some(0) => return Err(format!("{}: `work` must be at least 1 second", context)),
+                Some(work) => work,
+                None => return Err(format!("{}: missing `work`", context)),
+            };
+            let rest = spec.rest.unwrap_or(0);
+            let repeat = spec.repeat.unwrap_or(1);
+            if repeat == 0 {
+                return Err(format!("{}: `repeat` must be at least 1", context));
+            }
+            for _ in 0..repeat {
+                rounds.push(PlanRound { name: name.clone(), work, rest, image: spec.image.clone() });
+            }
+        }
+        if rounds.is_empty() {
+            return Err(format!("{}: the plan has no [[block]] entries", path.display()));
+        }
+        Ok(Plan { blocks: file.block.len(), rounds })
+    }
+}
+
+fn read_plan_file(path: &Path) -> Result<PlanFile, String> {
+    let data = fs::read_to_string(path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    // Parse errors already name the line, column and offending field
+    toml::from_str(&data).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn collect_definitions(
+    path: &Path,
+    file: &PlanFile,
+    definitions: &mut HashMap<String, BlockSpec>,
+    depth: usize,
+) -> Result<(), String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("{}: includes nested too deeply", path.display()));
+    }
+    for include in &file.include {
+        let include_path = path.parent().unwrap_or(Path::new(".")).join(include);
+        let included = read_plan_file(&include_path)?;
+        collect_definitions(&include_path, &included, definitions, depth + 1)?;
+    }
+    // Later definitions win, so a plan can override a block from an include
+    for spec in &file.block {
+        if let Some(name) = &spec.name
+            && spec.use_block.is_none()
+        {
+            definitions.insert(name.clone(), BlockSpec { image: resolve_image(path, &spec.image), ..spec.clone() });
+        }
+    }
+    Ok(())
+}
+
+fn resolve_image(path: &Path, image: &Option<String>) -> Option<String> {
+    let image = image.as_deref()?;
+    Some(path.parent().unwrap_or(Path::new(".")).join(image).to_string_lossy().into_owned())
+}
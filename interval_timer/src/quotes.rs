@@ -0,0 +1,35 @@
+use std::fs;
+
+const BUILT_IN: &[&str] = &[
+    "Breathe. The next round is yours.",
+    "Slow is smooth, smooth is fast.",
+    "You don't have to be great to start.",
+    "Rest is part of the training.",
+    "One more round than yesterday.",
+    "Strong legs, calm mind.",
+    "Discipline is remembering what you want.",
+    "Small steps add up.",
+    "Shake it out, reset, go again.",
+    "The hard part is already behind you.",
+    "Form first, speed later.",
+    "Every rep counts.",
+];
+
+// The built-in quotes plus the non-empty lines of the user's file. A missing or unreadable
+// file only leaves the built-in ones.
+pub fn load(path: &str) -> Vec<String> {
+    let mut quotes: Vec<String> = BUILT_IN.iter().map(|quote| quote.to_string()).collect();
+    if path.trim().is_empty() {
+        return quotes;
+    }
+    match fs::read_to_string(path.trim()) {
+        Ok(data) => quotes.extend(data.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string)),
+        Err(err) => log::warn!("Could not read quotes from {}: {}", path, err),
+    }
+    quotes
+}
+
+// "{rounds}" and "{time}" in the completion message
+pub fn completion_message(template: &str, rounds: u32, time: &str) -> String {
+    template.replace("{rounds}", &rounds.to_string()).replace("{time}", time)
+}
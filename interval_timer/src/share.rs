@@ -0,0 +1,177 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+
+use crate::TimerMode;
+use crate::format;
+use crate::plan::PlanRound;
+
+// Bumped whenever the contents change in a way older versions can't read
+const PREFIX: &str = "IT1.";
+
+// The parts of the settings that describe a workout. Paths, commands and other
+// machine specific settings are never shared.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedConfig {
+    pub workout_duration: u64,
+    pub rest_duration: u64,
+    pub rounds: u32,
+    pub lead_up_duration: u64,
+    pub mode: TimerMode,
+    pub long_break_every: u32,
+    pub long_break_duration: u64,
+    #[serde(default)]
+    pub round_list: Vec<PlanRound>, // Empty when the uniform durations are used
+    #[serde(default)]
+    pub use_stations: bool,
+    #[serde(default)]
+    pub stations: Vec<String>,
+    #[serde(default)]
+    pub laps: u32,
+    #[serde(default)]
+    pub rotate_break: u64,
+}
+
+impl SharedConfig {
+    // The same rule as the timer's: only plain intervals go round stations
+    pub fn is_circuit(&self) -> bool {
+        self.use_stations && !self.stations.is_empty() && self.round_list.is_empty() && self.mode == TimerMode::Intervals
+    }
+
+    pub fn describe(&self) -> String {
+        let body = if self.is_circuit() {
+            format!(
+                "{} × {} ({}s work + {}s rest)",
+                format::count(self.stations.len() as u64, "station"),
+                format::count(self.laps as u64, "lap"),
+                self.workout_duration,
+                self.rest_duration
+            )
+        } else if self.round_list.is_empty() {
+            format!(
+                "{} × ({}s work + {}s rest)",
+                format::count(self.rounds as u64, "round"),
+                self.workout_duration,
+                self.rest_duration
+            )
+        } else {
+            format!("Round list with {}", format::count(self.round_list.len() as u64, "round"))
+        };
+        let mode = match self.mode {
+            TimerMode::Intervals => "",
+            TimerMode::Pomodoro => ", Pomodoro",
+            TimerMode::Stopwatch => ", stopwatch",
+        };
+        format!("{}, {}s lead-up{}", body, self.lead_up_duration, mode)
+    }
+
+    // Short enough for a button: "60/45×10", "Pomodoro 1500/300×4", "8 planned rounds"
+    pub fn short_label(&self) -> String {
+        let body = if !self.round_list.is_empty() {
+            format!("{} planned", format::count(self.round_list.len() as u64, "round"))
+        } else if self.is_circuit() {
+            format!("{}/{}, {}", self.workout_duration, self.rest_duration, format::count(self.stations.len() as u64, "station"))
+        } else {
+            format!("{}/{}×{}", self.workout_duration, self.rest_duration, self.rounds)
+        };
+        match self.mode {
+            TimerMode::Intervals => body,
+            TimerMode::Pomodoro => format!("Pomodoro {}", body),
+            TimerMode::Stopwatch => "Stopwatch".to_string(),
+        }
+    }
+}
+
+pub fn encode(config: &SharedConfig) -> String {
+    let json = serde_json::to_vec(config).unwrap_or_default();
+    format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(json))
+}
+
+pub fn decode(text: &str) -> Result<SharedConfig, String> {
+    let Some(data) = text.trim().strip_prefix(PREFIX) else {
+        return Err("This is not an interval timer config".to_string());
+    };
+    let json = URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|_| "The config is damaged or incomplete, copy it again".to_string())?;
+    serde_json::from_slice(&json).map_err(|err| format!("The config is damaged or incomplete ({})", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SharedConfig {
+        SharedConfig {
+            workout_duration: 60,
+            rest_duration: 45,
+            rounds: 10,
+            lead_up_duration: 5,
+            mode: TimerMode::Intervals,
+            long_break_every: 4,
+            long_break_duration: 120,
+            round_list: vec![PlanRound { name: "Squats".to_string(), work: 40, rest: 20, image: None }],
+            use_stations: false,
+            stations: Vec::new(),
+            laps: 3,
+            rotate_break: 120,
+        }
+    }
+
+    fn circuit() -> SharedConfig {
+        SharedConfig {
+            round_list: Vec::new(),
+            use_stations: true,
+            stations: vec!["Rower".to_string(), "Bike".to_string(), "Ski".to_string()],
+            laps: 2,
+            ..config()
+        }
+    }
+
+    #[test]
+    fn decode_reads_back_what_encode_wrote() {
+        let text = encode(&config());
+        assert!(text.starts_with(PREFIX));
+        assert!(decode(&format!("  {}\n", text)).unwrap() == config());
+    }
+
+    #[test]
+    fn decode_needs_the_prefix() {
+        let text = encode(&config());
+        let error = decode(text.strip_prefix(PREFIX).unwrap()).err();
+        assert_eq!(error.as_deref(), Some("This is not an interval timer config"));
+    }
+
+    #[test]
+    fn decode_rejects_bad_base64() {
+        let error = decode("IT1.not*base64!").err();
+        assert_eq!(error.as_deref(), Some("The config is damaged or incomplete, copy it again"));
+    }
+
+    #[test]
+    fn decode_rejects_truncated_json() {
+        let json = serde_json::to_vec(&config()).unwrap();
+        let text = format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(&json[..json.len() / 2]));
+        let error = decode(&text).err().unwrap();
+        assert!(error.starts_with("The config is damaged or incomplete ("), "{}", error);
+    }
+
+    #[test]
+    fn stations_survive_the_round_trip() {
+        let decoded = decode(&encode(&circuit())).unwrap();
+        assert!(decoded == circuit());
+        assert_eq!(decoded.describe(), "3 stations × 2 laps (60s work + 45s rest), 5s lead-up");
+        assert_eq!(decoded.short_label(), "60/45, 3 stations");
+    }
+
+    #[test]
+    fn configs_from_before_stations_are_not_circuits() {
+        let mut json = serde_json::to_value(config()).unwrap();
+        for key in ["use_stations", "stations", "laps", "rotate_break"] {
+            json.as_object_mut().unwrap().remove(key);
+        }
+        let text = format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(json.to_string()));
+        let decoded = decode(&text).unwrap();
+        assert!(!decoded.use_stations && !decoded.is_circuit());
+    }
+}
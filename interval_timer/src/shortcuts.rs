@@ -0,0 +1,348 @@
+use eframe::egui;
+use std::collections::BTreeMap;
+
+// Remapped keys from the settings, binding name to key name. An empty key name leaves the
+// action without a key, actions not in here keep their default keys.
+pub type Bindings = BTreeMap<String, String>;
+
+// What a key press, a palette entry or a context menu item does, handled in WorkoutTimer::run_action
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Palette,
+    ToggleHelp,
+    Fullscreen,
+    Escape, // Closes the help, or leaves the focused control
+    StartPause,
+    Stop,
+    Skip,
+    RestartPhase,
+    Extend, // By the rest extension
+    AddRound,
+    Lap,
+    Adjust(i64), // Seconds, only while paused
+    Undo,
+    Redo,
+    BackupHistory, // To the backup file set under History
+    Preview(u32), // Runs the session at this many times real time, 1 is back to normal
+}
+
+// Everything the command palette offers, in this order until something is typed
+pub const COMMANDS: &[Action] = &[
+    Action::StartPause,
+    Action::Stop,
+    Action::Skip,
+    Action::RestartPhase,
+    Action::Extend,
+    Action::AddRound,
+    Action::Lap,
+    Action::Adjust(5),
+    Action::Adjust(-5),
+    Action::Undo,
+    Action::Redo,
+    Action::Fullscreen,
+    Action::BackupHistory,
+    Action::ToggleHelp,
+    Action::Preview(10),
+    Action::Preview(30),
+    Action::Preview(1),
+];
+
+impl Action {
+    // For the palette and the context menu, WorkoutTimer::command_name fills in the ones that
+    // depend on the state
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Palette => "Command palette",
+            Action::ToggleHelp => "Keyboard help",
+            Action::Fullscreen => "Toggle fullscreen",
+            Action::Escape => "Close",
+            Action::StartPause => "Start / pause",
+            Action::Stop => "Stop",
+            Action::Skip => "Skip phase",
+            Action::RestartPhase => "Restart phase",
+            Action::Extend => "Extend rest",
+            Action::AddRound => "Add a round",
+            Action::Lap => "Lap",
+            Action::Adjust(_) => "Adjust the time left",
+            Action::Undo => "Undo settings change",
+            Action::Redo => "Redo settings change",
+            Action::BackupHistory => "Export history to the backup file",
+            Action::Preview(_) => "Preview speed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scope {
+    Always,
+    Leader, // Off while following another timer
+    Unfocused, // Also off while a control has focus, which uses the key itself
+}
+
+pub struct Shortcut {
+    pub keys: &'static [egui::Key], // Any of them, unless remapped
+    pub binding: Option<&'static str>, // Name in the settings, for the ones that can be remapped
+    pub shift: Option<bool>, // None when Shift doesn't matter, e.g. for "+" and "?"
+    pub command: bool, // Ctrl, or Cmd on macOS
+    pub action: Action,
+    pub scope: Scope,
+    pub description: &'static str,
+}
+
+// The one list both the key handling and the help window go by
+pub const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        keys: &[egui::Key::K],
+        binding: None,
+        shift: Some(false),
+        command: true,
+        action: Action::Palette,
+        scope: Scope::Always,
+        description: "search every action",
+    },
+    Shortcut {
+        keys: &[egui::Key::F1, egui::Key::Questionmark],
+        binding: None,
+        shift: None,
+        command: false,
+        action: Action::ToggleHelp,
+        scope: Scope::Always,
+        description: "show or hide this help",
+    },
+    Shortcut {
+        keys: &[egui::Key::F11],
+        binding: Some("fullscreen"),
+        shift: None,
+        command: false,
+        action: Action::Fullscreen,
+        scope: Scope::Always,
+        description: "fullscreen on or off",
+    },
+    Shortcut {
+        keys: &[egui::Key::Escape],
+        binding: None,
+        shift: None,
+        command: false,
+        action: Action::Escape,
+        scope: Scope::Always,
+        description: "close this help, or leave the focused control",
+    },
+    Shortcut {
+        keys: &[egui::Key::Plus, egui::Key::Equals],
+        binding: None,
+        shift: None,
+        command: false,
+        action: Action::AddRound,
+        scope: Scope::Leader,
+        description: "one more round",
+    },
+    Shortcut {
+        keys: &[egui::Key::L],
+        binding: None,
+        shift: None,
+        command: false,
+        action: Action::Lap,
+        scope: Scope::Leader,
+        description: "lap, in stopwatch mode",
+    },
+    Shortcut {
+        keys: &[egui::Key::S],
+        binding: Some("stop"),
+        shift: None,
+        command: false,
+        action: Action::Stop,
+        scope: Scope::Leader,
+        description: "stop the session",
+    },
+    Shortcut {
+        keys: &[egui::Key::N],
+        binding: Some("skip"),
+        shift: None,
+        command: false,
+        action: Action::Skip,
+        scope: Scope::Leader,
+        description: "skip to the next phase",
+    },
+    Shortcut {
+        keys: &[egui::Key::R],
+        binding: Some("restart_phase"),
+        shift: None,
+        command: false,
+        action: Action::RestartPhase,
+        scope: Scope::Leader,
+        description: "restart the current phase",
+    },
+    Shortcut {
+        keys: &[egui::Key::E],
+        binding: Some("extend"),
+        shift: None,
+        command: false,
+        action: Action::Extend,
+        scope: Scope::Leader,
+        description: "add the rest extension to the current rest",
+    },
+    Shortcut {
+        keys: &[egui::Key::Space],
+        binding: Some("start_pause"),
+        shift: None,
+        command: false,
+        action: Action::StartPause,
+        scope: Scope::Unfocused,
+        description: "start, pause or resume",
+    },
+    Shortcut {
+        keys: &[egui::Key::ArrowUp],
+        binding: None,
+        shift: Some(false),
+        command: false,
+        action: Action::Adjust(5),
+        scope: Scope::Unfocused,
+        description: "+5 s while paused",
+    },
+    Shortcut {
+        keys: &[egui::Key::ArrowDown],
+        binding: None,
+        shift: Some(false),
+        command: false,
+        action: Action::Adjust(-5),
+        scope: Scope::Unfocused,
+        description: "-5 s while paused",
+    },
+    Shortcut {
+        keys: &[egui::Key::ArrowUp],
+        binding: None,
+        shift: Some(true),
+        command: false,
+        action: Action::Adjust(1),
+        scope: Scope::Unfocused,
+        description: "+1 s while paused",
+    },
+    Shortcut {
+        keys: &[egui::Key::ArrowDown],
+        binding: None,
+        shift: Some(true),
+        command: false,
+        action: Action::Adjust(-1),
+        scope: Scope::Unfocused,
+        description: "-1 s while paused",
+    },
+    Shortcut {
+        keys: &[egui::Key::Z],
+        binding: None,
+        shift: Some(false),
+        command: true,
+        action: Action::Undo,
+        scope: Scope::Always,
+        description: "undo a settings change, while idle",
+    },
+    Shortcut {
+        keys: &[egui::Key::Z],
+        binding: None,
+        shift: Some(true),
+        command: true,
+        action: Action::Redo,
+        scope: Scope::Always,
+        description: "redo it",
+    },
+];
+
+impl Shortcut {
+    pub fn keys(&self, bindings: &Bindings) -> Vec<egui::Key> {
+        match self.binding.and_then(|name| bindings.get(name)) {
+            Some(key) => egui::Key::from_name(key).into_iter().collect(),
+            None => self.keys.to_vec(),
+        }
+    }
+
+    pub fn pressed(&self, input: &egui::InputState, bindings: &Bindings) -> bool {
+        self.shift.is_none_or(|shift| input.modifiers.shift == shift)
+            && input.modifiers.command == self.command
+            && self.keys(bindings).iter().any(|key| input.key_pressed(*key))
+    }
+
+    // "Shift+⏶", "Ctrl+Z", "+ / =", empty without a key
+    pub fn label(&self, bindings: &Bindings) -> String {
+        let keys = self.keys(bindings);
+        if keys.is_empty() {
+            return String::new();
+        }
+        let names: Vec<&str> = keys.iter().map(|key| key.symbol_or_name()).collect();
+        let command = match self.command {
+            true if cfg!(target_os = "macos") => "Cmd+",
+            true => "Ctrl+",
+            false => "",
+        };
+        let shift = if self.shift == Some(true) { "Shift+" } else { "" };
+        format!("{}{}{}", command, shift, names.join(" / "))
+    }
+
+    // The description as a settings row label, "Stop the session"
+    pub fn title(&self) -> String {
+        let mut chars = self.description.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+    }
+
+    // Up and Shift+Up, or Z and Ctrl+Z, share a key without getting in each other's way
+    fn overlaps(&self, other: &Shortcut, key: egui::Key, bindings: &Bindings) -> bool {
+        let shift_differs = matches!((self.shift, other.shift), (Some(a), Some(b)) if a != b);
+        !shift_differs && self.command == other.command && other.keys(bindings).contains(&key)
+    }
+}
+
+// The keys for an action, for tooltips
+pub fn label_of(action: Action, bindings: &Bindings) -> String {
+    SHORTCUTS.iter().find(|shortcut| shortcut.action == action).map(|shortcut| shortcut.label(bindings)).unwrap_or_default()
+}
+
+// What else already uses a key, so a binding never takes a key twice
+pub fn conflict(bindings: &Bindings, binding: &str, key: egui::Key) -> Option<&'static Shortcut> {
+    let this = SHORTCUTS.iter().find(|shortcut| shortcut.binding == Some(binding))?;
+    SHORTCUTS
+        .iter()
+        .filter(|other| other.binding != Some(binding))
+        .find(|other| this.overlaps(other, key, bindings))
+}
+
+// Mistakes in a hand-edited settings file, one line each
+pub fn problems(bindings: &Bindings) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, key) in bindings {
+        let Some(shortcut) = SHORTCUTS.iter().find(|shortcut| shortcut.binding == Some(name.as_str())) else {
+            problems.push(format!("Unknown shortcut \"{}\"", name));
+            continue;
+        };
+        let Some(key) = egui::Key::from_name(key) else {
+            if !key.is_empty() {
+                problems.push(format!("Unknown key \"{}\" for {}", key, shortcut.description));
+            }
+            continue;
+        };
+        if let Some(other) = conflict(bindings, name, key) {
+            problems.push(format!("{} is used to {} and to {}", key.symbol_or_name(), shortcut.description, other.description));
+        }
+    }
+    problems
+}
+
+// How well a search matches a name: every character of the query in order, better for runs of
+// characters and for matches at the start of words. None when it doesn't match.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let mut query = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut previous_matched = false;
+    let mut word_start = true;
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let Some(&wanted) = query.peek() else {
+            break;
+        };
+        if c == wanted {
+            query.next();
+            score += 1 + if previous_matched { 3 } else { 0 } + if word_start { 2 } else { 0 };
+            previous_matched = true;
+        } else {
+            previous_matched = false;
+        }
+        word_start = !c.is_alphanumeric();
+    }
+    query.peek().is_none().then_some(score)
+}
@@ -0,0 +1,230 @@
+use rodio::decoder::DecoderError;
+use rodio::source::SineWave;
+use rodio::{Decoder, Source};
+use std::borrow::Cow;
+use std::fs;
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+use std::time::Duration;
+
+const TONE_SAMPLE_RATE: u32 = 22050;
+const TONE_SECS: f32 = 0.15;
+const TONE_GAP_SECS: f32 = 0.05;
+const FALLBACK_FREQ: f32 = 880.0;
+const FALLBACK_BEEP: Duration = Duration::from_millis(150);
+const FALLBACK_GAP: Duration = Duration::from_millis(100);
+const FALLBACK_LEVEL: f32 = 0.3; // Raw sine waves are much louder than the recorded cues
+
+// Builds without the embedded-sounds feature only have the synthesized pack
+pub enum Clip {
+    #[cfg(feature = "embedded-sounds")]
+    Embedded(&'static [u8]),
+    Tones(&'static [f32]), // Short beeps at these frequencies, one after another
+}
+
+// Cue sounds of the binary. Adding a pack only needs a new entry in SOUND_PACKS.
+pub struct SoundPack {
+    pub name: &'static str,
+    pub work_start: Clip,
+    pub work_end: Clip,
+    pub complete: Clip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cue {
+    WorkStart,  // Out of the lead-up or a rest
+    WorkEnd,    // Rest begins
+    FinalRound, // Work start of the last round, played twice
+    LongBreak,  // Pomodoro long break, the work-end cue played twice
+    Rotate,     // Break between station laps, the work-end cue played three times
+    Complete,
+}
+
+impl Cue {
+    // Which of the custom sound files replaces this cue: work start, work end or complete
+    pub fn slot(self) -> usize {
+        match self {
+            Cue::WorkStart | Cue::FinalRound => 0,
+            Cue::WorkEnd | Cue::LongBreak | Cue::Rotate => 1,
+            Cue::Complete => 2,
+        }
+    }
+
+    // Stands in for the cue when its sound won't decode: one beep as rest ends, two as work
+    // ends, three at the end of the session
+    pub fn fallback_beeps(self) -> usize {
+        match self {
+            Cue::WorkStart | Cue::FinalRound => 1,
+            Cue::WorkEnd | Cue::LongBreak | Cue::Rotate => 2,
+            Cue::Complete => 3,
+        }
+    }
+}
+
+impl SoundPack {
+    // Encoded audio for the decoder, synthesized packs are rendered to WAV on the spot
+    pub fn clip(&self, cue: Cue) -> Cow<'static, [u8]> {
+        let clip = match cue {
+            Cue::WorkStart | Cue::FinalRound => &self.work_start,
+            Cue::WorkEnd | Cue::LongBreak | Cue::Rotate => &self.work_end,
+            Cue::Complete => &self.complete,
+        };
+        match clip {
+            #[cfg(feature = "embedded-sounds")]
+            Clip::Embedded(data) => Cow::Borrowed(data),
+            Clip::Tones(freqs) => Cow::Owned(tones_wav(freqs)),
+        }
+    }
+}
+
+pub const SOUND_PACKS: &[SoundPack] = &[
+    #[cfg(feature = "embedded-sounds")]
+    SoundPack {
+        name: "Chimes",
+        work_start: Clip::Embedded(include_bytes!("../rest_finish.mp3")),
+        work_end: Clip::Embedded(include_bytes!("../work_finish.mp3")),
+        complete: Clip::Embedded(include_bytes!("../complete_finish.mp3")),
+    },
+    #[cfg(feature = "embedded-sounds")]
+    SoundPack {
+        name: "Boxing bell",
+        work_start: Clip::Embedded(include_bytes!("../sounds/boxing_bell_work_start.wav")),
+        work_end: Clip::Embedded(include_bytes!("../sounds/boxing_bell_work_end.wav")),
+        complete: Clip::Embedded(include_bytes!("../sounds/boxing_bell_complete.wav")),
+    },
+    #[cfg(feature = "embedded-sounds")]
+    SoundPack {
+        name: "Soft beeps",
+        work_start: Clip::Embedded(include_bytes!("../sounds/soft_beeps_work_start.wav")),
+        work_end: Clip::Embedded(include_bytes!("../sounds/soft_beeps_work_end.wav")),
+        complete: Clip::Embedded(include_bytes!("../sounds/soft_beeps_complete.wav")),
+    },
+    SoundPack {
+        name: "Synth beeps",
+        work_start: Clip::Tones(&[660.0, 880.0]),
+        work_end: Clip::Tones(&[880.0, 660.0]),
+        complete: Clip::Tones(&[660.0, 880.0, 1100.0, 1320.0]),
+    },
+];
+
+// Straight from sine waves, so it plays even when no decoder on the system works
+pub fn fallback_sound(cue: Cue) -> impl Source<Item = f32> + Send {
+    let beeps = (0..cue.fallback_beeps())
+        .map(|_| SineWave::new(FALLBACK_FREQ).take_duration(FALLBACK_BEEP).delay(FALLBACK_GAP));
+    rodio::source::from_iter(beeps).amplify(FALLBACK_LEVEL)
+}
+
+// Unknown names (e.g. a pack removed in a later version) fall back to the first pack
+pub fn pack(name: &str) -> &'static SoundPack {
+    SOUND_PACKS.iter().find(|pack| pack.name == name).unwrap_or(&SOUND_PACKS[0])
+}
+
+const VOICE_EXTENSIONS: &[&str] = &["wav", "ogg", "mp3"];
+
+// Recorded words for announcements: round.wav, 1.wav ... 50.wav (or .ogg / .mp3) in one folder
+pub fn voice_clip(dir: &Path, word: &str) -> Option<Vec<u8>> {
+    VOICE_EXTENSIONS
+        .iter()
+        .find_map(|ext| fs::read(dir.join(format!("{}.{}", word, ext))).ok())
+}
+
+// Reads a user's sound file and checks that it decodes (MP3, WAV, OGG Vorbis or FLAC), so a bad
+// file is reported when it's picked rather than failing quietly at the next cue
+pub fn load_custom(path: &str) -> Result<Vec<u8>, String> {
+    let data = fs::read(path.trim()).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => "File not found".to_string(),
+        _ => format!("Can't read the file: {}", err),
+    })?;
+    match Decoder::new(Cursor::new(data.clone())) {
+        Ok(_) => Ok(data),
+        Err(DecoderError::UnrecognizedFormat) => Err("Unsupported format, use MP3, WAV, OGG or FLAC".to_string()),
+        Err(err) => Err(format!("Corrupt file: {}", err)),
+    }
+}
+
+// 16-bit mono PCM in a WAV container, so synthesized cues go through the same decoder path
+fn tones_wav(freqs: &[f32]) -> Vec<u8> {
+    let tone_len = (TONE_SAMPLE_RATE as f32 * TONE_SECS) as usize;
+    let gap_len = (TONE_SAMPLE_RATE as f32 * TONE_GAP_SECS) as usize;
+    let fade_len = tone_len / 10; // Avoids clicks at the edges
+    let mut samples: Vec<i16> = Vec::new();
+    for freq in freqs {
+        for i in 0..tone_len {
+            let edge = i.min(tone_len - 1 - i);
+            let envelope = (edge as f32 / fade_len as f32).min(1.0);
+            let t = i as f32 / TONE_SAMPLE_RATE as f32;
+            let value = (t * freq * std::f32::consts::TAU).sin() * envelope * 0.3;
+            samples.push((value * i16::MAX as f32) as i16);
+        }
+        samples.extend(std::iter::repeat_n(0, gap_len));
+    }
+
+    let data_len = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    wav.extend_from_slice(&TONE_SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(TONE_SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/interval_timer/test-data/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn load_custom_returns_the_file_when_it_decodes() {
+        let data = load_custom(&fixture("beep.wav")).unwrap();
+        assert_eq!(data, fs::read(fixture("beep.wav")).unwrap());
+        assert!(load_custom(&format!(" {} ", fixture("beep.wav"))).is_ok());
+    }
+
+    #[test]
+    fn load_custom_reports_missing_files() {
+        assert_eq!(load_custom(&fixture("missing.wav")), Err("File not found".to_string()));
+    }
+
+    #[test]
+    fn load_custom_reports_unsupported_formats() {
+        let error = load_custom(&fixture("notes.txt"));
+        assert_eq!(error, Err("Unsupported format, use MP3, WAV, OGG or FLAC".to_string()));
+    }
+
+    #[test]
+    fn load_custom_reports_corrupt_files() {
+        let error = load_custom(&fixture("corrupt.mp3")).unwrap_err();
+        assert!(error.starts_with("Corrupt file: "), "{}", error);
+    }
+
+    #[test]
+    fn every_pack_decodes() {
+        for pack in SOUND_PACKS {
+            for cue in [Cue::WorkStart, Cue::WorkEnd, Cue::Complete] {
+                assert!(Decoder::new(Cursor::new(pack.clip(cue).into_owned())).is_ok(), "{}", pack.name);
+            }
+        }
+    }
+
+    #[test]
+    fn fallback_sound_beeps_once_per_fallback_beep() {
+        let beep = SineWave::new(FALLBACK_FREQ).take_duration(FALLBACK_BEEP).delay(FALLBACK_GAP).count();
+        for cue in [Cue::WorkStart, Cue::WorkEnd, Cue::Complete] {
+            assert_eq!(fallback_sound(cue).count(), beep * cue.fallback_beeps());
+        }
+    }
+}
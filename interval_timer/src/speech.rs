@@ -0,0 +1,164 @@
+use std::io::ErrorKind;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+const REAP_INTERVAL: Duration = Duration::from_secs(1);
+
+// None cancels whatever is being said
+type Request = Option<(String, f32)>;
+
+// Speaks through the text-to-speech the OS already has: SAPI on Windows, `say` on macOS and
+// speech-dispatcher or espeak elsewhere. The engines run as child processes from a worker thread,
+// so speaking never holds up a frame, and a machine without any engine just stays quiet.
+#[derive(Default)]
+pub struct Speaker {
+    tx: Option<Sender<Request>>,
+}
+
+impl Speaker {
+    // Volume from 0 to 1, silent at 0
+    pub fn speak(&mut self, text: &str, volume: f32) {
+        if volume <= 0.0 || text.trim().is_empty() {
+            return;
+        }
+        let tx = self.tx.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || worker(rx));
+            tx
+        });
+        let _ = tx.send(Some((text.trim().to_string(), volume.min(1.0))));
+    }
+
+    pub fn cancel(&self) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(None);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    #[cfg(windows)]
+    Sapi,
+    #[cfg(target_os = "macos")]
+    Say,
+    #[cfg(not(any(windows, target_os = "macos")))]
+    SpdSay,
+    #[cfg(not(any(windows, target_os = "macos")))]
+    Espeak,
+}
+
+// In order of preference, the first one that starts is kept
+#[cfg(windows)]
+const ENGINES: &[Engine] = &[Engine::Sapi];
+#[cfg(target_os = "macos")]
+const ENGINES: &[Engine] = &[Engine::Say];
+#[cfg(not(any(windows, target_os = "macos")))]
+const ENGINES: &[Engine] = &[Engine::SpdSay, Engine::Espeak];
+
+impl Engine {
+    fn command(self, text: &str, volume: f32) -> Command {
+        let mut cmd = match self {
+            #[cfg(windows)]
+            Engine::Sapi => {
+                // The text goes in through the environment, so no quoting can break the script
+                let mut cmd = Command::new("powershell");
+                cmd.args([
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    "Add-Type -AssemblyName System.Speech; \
+                     $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+                     $s.Volume = [int]$env:INTERVAL_TIMER_VOLUME; $s.Speak($env:INTERVAL_TIMER_SPEECH)",
+                ]);
+                cmd.env("INTERVAL_TIMER_SPEECH", text)
+                    .env("INTERVAL_TIMER_VOLUME", ((volume * 100.0) as u32).to_string());
+                {
+                    use std::os::windows::process::CommandExt;
+                    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+                    cmd.creation_flags(CREATE_NO_WINDOW);
+                }
+                cmd
+            }
+            #[cfg(target_os = "macos")]
+            Engine::Say => {
+                let mut cmd = Command::new("say");
+                cmd.arg(format!("[[volm {:.2}]] {}", volume, text));
+                cmd
+            }
+            #[cfg(not(any(windows, target_os = "macos")))]
+            Engine::SpdSay => {
+                // -w keeps the process around while speaking, so there is something to cancel
+                let mut cmd = Command::new("spd-say");
+                cmd.args(["-w", "-i", &((volume * 200.0) as i32 - 100).to_string(), "--", text]);
+                cmd
+            }
+            #[cfg(not(any(windows, target_os = "macos")))]
+            Engine::Espeak => {
+                let mut cmd = Command::new("espeak");
+                cmd.args(["-a", &((volume * 200.0) as u32).to_string(), "--", text]);
+                cmd
+            }
+        };
+        cmd.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        cmd
+    }
+
+    fn stop(self, mut child: Child) {
+        let _ = child.kill();
+        let _ = child.wait();
+        // The speech-dispatcher daemon does the talking, not the killed client
+        #[cfg(not(any(windows, target_os = "macos")))]
+        if let Engine::SpdSay = self {
+            let _ = Command::new("spd-say").arg("-S").stdout(Stdio::null()).stderr(Stdio::null()).status();
+        }
+    }
+}
+
+fn worker(rx: Receiver<Request>) {
+    let mut engines = ENGINES.to_vec();
+    let mut speaking: Option<(Engine, Child)> = None;
+    loop {
+        let request = match rx.recv_timeout(REAP_INTERVAL) {
+            Ok(request) => request,
+            Err(RecvTimeoutError::Timeout) => {
+                // Done speaking, collect the exit status
+                if let Some((_, child)) = &mut speaking
+                    && !matches!(child.try_wait(), Ok(None))
+                {
+                    speaking = None;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        // A new phase talks over nothing, the previous announcement stops first
+        if let Some((engine, child)) = speaking.take() {
+            engine.stop(child);
+        }
+        let Some((text, volume)) = request else {
+            continue;
+        };
+        while let Some(&engine) = engines.first() {
+            match engine.command(&text, volume).spawn() {
+                Ok(child) => {
+                    speaking = Some((engine, child));
+                    break;
+                }
+                Err(err) => {
+                    if err.kind() == ErrorKind::NotFound {
+                        log::info!("No {:?} text-to-speech on this system", engine);
+                    } else {
+                        log::warn!("Text-to-speech with {:?} failed: {}", engine, err);
+                    }
+                    engines.remove(0); // Not tried again this run
+                }
+            }
+        }
+    }
+    if let Some((engine, child)) = speaking {
+        engine.stop(child);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const EMIT_INTERVAL: Duration = Duration::from_secs(1);
+const QUEUE_SIZE: usize = 8;
+
+// Streams one JSON status line per second to stdout ("-") or a FIFO / named pipe path.
+// Lines go through a bounded queue to a writer thread, so a slow reader can never block the UI.
+#[derive(Default)]
+pub struct StatusOutput {
+    target: String,
+    tx: Option<SyncSender<String>>,
+    closed: bool,
+    last_emit: Option<Instant>,
+}
+
+impl StatusOutput {
+    pub fn update(&mut self, target: &str, line: String) {
+        if target != self.target {
+            self.target = target.to_string();
+            self.tx = None;
+            self.closed = false;
+        }
+        if self.target.is_empty() || self.closed {
+            return;
+        }
+        if self.last_emit.is_some_and(|t| t.elapsed() < EMIT_INTERVAL) {
+            return;
+        }
+        self.last_emit = Some(Instant::now());
+
+        let tx = self.tx.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::sync_channel(QUEUE_SIZE);
+            let target = target.to_string();
+            thread::spawn(move || writer(&target, rx));
+            tx
+        });
+        match tx.try_send(line) {
+            Ok(()) | Err(TrySendError::Full(_)) => {} // Nobody reading right now, drop the line
+            Err(TrySendError::Disconnected(_)) => {
+                // The writer gave up because the reader went away; stay quiet until the target changes
+                log::info!("Status output reader disconnected, stopping");
+                self.tx = None;
+                self.closed = true;
+            }
+        }
+    }
+}
+
+fn writer(target: &str, rx: Receiver<String>) {
+    let mut out: Box<dyn Write> = if target == "-" {
+        Box::new(io::stdout())
+    } else {
+        // Opening a FIFO blocks until a reader shows up, which is fine on this thread
+        match OpenOptions::new().create(true).append(true).open(target) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                log::warn!("Can't open status output {}: {}", target, err);
+                return;
+            }
+        }
+    };
+    for line in rx {
+        if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::TimerState;
+
+const SEND_INTERVAL: Duration = Duration::from_millis(250);
+const LOST_AFTER: Duration = Duration::from_secs(3);
+const MAGIC: &str = "interval_timer_sync";
+
+// Everything a follower needs to draw the leader's timer
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncPacket {
+    pub state: TimerState,
+    pub phase: String,
+    pub color: [u8; 3],
+    pub round: u32,
+    pub rounds: u32,
+    pub phase_duration: u64,
+    pub remaining_ms: u64,
+    pub sent_at_ms: u64, // Leader's clock, LAN machines usually agree closely enough through NTP
+}
+
+impl SyncPacket {
+    // Counts on from the leader's timestamp, so followers don't lag by the network delay
+    pub fn remaining_ms_now(&self) -> u64 {
+        if self.state.is_paused() || self.state == TimerState::Idle {
+            return self.remaining_ms;
+        }
+        let age = unix_ms().saturating_sub(self.sent_at_ms).min(LOST_AFTER.as_millis() as u64);
+        self.remaining_ms.saturating_sub(age)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    magic: String,
+    packet: SyncPacket,
+}
+
+pub fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+// Broadcasts the timer to the local network a few times a second
+#[derive(Default)]
+pub struct SyncLeader {
+    socket: Option<UdpSocket>,
+    last_sent: Option<Instant>,
+}
+
+impl SyncLeader {
+    pub fn due(&self) -> bool {
+        self.last_sent.is_none_or(|t| t.elapsed() >= SEND_INTERVAL)
+    }
+
+    pub fn send(&mut self, port: u16, packet: SyncPacket) {
+        self.last_sent = Some(Instant::now());
+        if self.socket.is_none() {
+            self.socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+                .and_then(|socket| socket.set_broadcast(true).map(|_| socket))
+                .map_err(|err| log::warn!("Sync socket unavailable: {}", err))
+                .ok();
+        }
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        let envelope = Envelope { magic: MAGIC.to_string(), packet };
+        if let Ok(data) = serde_json::to_vec(&envelope) {
+            // A dropped packet is replaced by the next one a moment later
+            let _ = socket.send_to(&data, (Ipv4Addr::BROADCAST, port));
+        }
+    }
+}
+
+// Listens for a leader on a background thread, the UI only looks at the latest packet
+pub struct SyncFollower {
+    rx: Receiver<(SyncPacket, SocketAddr)>,
+    latest: Option<(SyncPacket, SocketAddr, Instant)>,
+    stop: Arc<AtomicBool>,
+}
+
+impl SyncFollower {
+    pub fn start(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            while !thread_stop.load(Ordering::Relaxed) {
+                let Ok((len, from)) = socket.recv_from(&mut buf) else {
+                    continue; // Timeout, check whether to stop
+                };
+                let Ok(envelope) = serde_json::from_slice::<Envelope>(&buf[..len]) else {
+                    continue;
+                };
+                if envelope.magic == MAGIC && tx.send((envelope.packet, from)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self { rx, latest: None, stop })
+    }
+
+    pub fn poll(&mut self) {
+        if let Some((packet, from)) = self.rx.try_iter().last() {
+            self.latest = Some((packet, from, Instant::now()));
+        }
+    }
+
+    pub fn latest(&self) -> Option<(&SyncPacket, SocketAddr)> {
+        self.latest.as_ref().map(|(packet, from, _)| (packet, *from))
+    }
+
+    pub fn connected(&self) -> bool {
+        self.latest.as_ref().is_some_and(|(_, _, received)| received.elapsed() < LOST_AFTER)
+    }
+}
+
+impl Drop for SyncFollower {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed); // Frees the port for the next follower
+    }
+}
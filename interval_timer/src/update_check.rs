@@ -0,0 +1,81 @@
+use serde::Deserialize;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+#[cfg(feature = "update-check")]
+use std::time::Duration;
+
+#[cfg(feature = "update-check")]
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/AceOfElse/Interval_Timer_Rust/releases/latest";
+#[cfg(feature = "update-check")]
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Release {
+    pub version: String,
+    pub url: String,
+}
+
+#[derive(Deserialize)]
+struct LatestRelease {
+    tag_name: String,
+    html_url: String,
+}
+
+// Runs the request on its own thread, the UI only ever polls for a result
+#[derive(Default)]
+pub struct UpdateChecker {
+    rx: Option<Receiver<Release>>,
+}
+
+impl UpdateChecker {
+    pub fn start(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Offline, rate limited or anything else: no banner, just a line in the log
+            match fetch_latest() {
+                Ok(release) if is_newer(&release.tag_name, env!("CARGO_PKG_VERSION")) => {
+                    let _ = tx.send(Release { version: release.tag_name, url: release.html_url });
+                }
+                Ok(_) => log::debug!("Update check: up to date"),
+                Err(err) => log::debug!("Update check failed: {}", err),
+            }
+        });
+        self.rx = Some(rx);
+    }
+
+    pub fn poll(&mut self) -> Option<Release> {
+        let release = self.rx.as_ref()?.try_recv().ok();
+        if release.is_some() {
+            self.rx = None;
+        }
+        release
+    }
+}
+
+#[cfg(not(feature = "update-check"))]
+fn fetch_latest() -> Result<LatestRelease, String> {
+    Err("built without the update-check feature".to_string())
+}
+
+#[cfg(feature = "update-check")]
+fn fetch_latest() -> Result<LatestRelease, String> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let body = agent
+        .get(LATEST_RELEASE_URL)
+        .set("User-Agent", concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .map_err(|err| err.to_string())?
+        .into_string()
+        .map_err(|err| err.to_string())?;
+    serde_json::from_str(&body).map_err(|err| err.to_string())
+}
+
+// Tags look like "v1.2.0", anything that isn't a plain version is never newer
+fn is_newer(tag: &str, current: &str) -> bool {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        version.trim_start_matches('v').split('.').map(|part| part.parse().ok()).collect()
+    };
+    match (parse(tag), parse(current)) {
+        (Some(tag), Some(current)) => tag > current,
+        _ => false,
+    }
+}
@@ -0,0 +1,122 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+
+const SCHEME: &str = "interval-timer";
+// Remembers which executable the scheme points at, so it is only registered again after a move
+const REGISTERED_FILE: &str = "url-scheme.txt";
+
+// interval-timer://start?work=40&rest=20&rounds=8 starts right away,
+// interval-timer://open?... only applies the values
+#[derive(Debug, Default)]
+pub struct LaunchRequest {
+    pub work: Option<u64>,
+    pub rest: Option<u64>,
+    pub rounds: Option<u32>,
+    pub lead_up: Option<u64>,
+    pub start: bool,
+}
+
+pub fn find_in_args() -> Option<String> {
+    env::args().find(|arg| arg.starts_with(&format!("{}:", SCHEME)))
+}
+
+pub fn parse(url: &str) -> Result<LaunchRequest, String> {
+    let rest = url
+        .strip_prefix(&format!("{}://", SCHEME))
+        .ok_or_else(|| format!("Not an {} link", SCHEME))?;
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut request = LaunchRequest {
+        start: match action.trim_end_matches('/') {
+            "start" => true,
+            "open" => false,
+            other => return Err(format!("Unknown link action \"{}\", use start or open", other)),
+        },
+        ..Default::default()
+    };
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let invalid = || format!("Invalid value \"{}\" for {} in the link", value, key);
+        match key {
+            "work" => request.work = Some(value.parse().map_err(|_| invalid())?),
+            "rest" => request.rest = Some(value.parse().map_err(|_| invalid())?),
+            "rounds" => request.rounds = Some(value.parse().map_err(|_| invalid())?),
+            "leadup" | "lead_up" => request.lead_up = Some(value.parse().map_err(|_| invalid())?),
+            _ => log::warn!("Ignoring unknown link parameter {}", key), // Links from newer versions
+        }
+    }
+    Ok(request)
+}
+
+// Makes the OS hand interval-timer:// links to this executable. Done on the first run and
+// whenever the executable moved, failures only mean links won't open the app.
+pub fn register() {
+    let Ok(exe) = env::current_exe() else {
+        return;
+    };
+    let marker = paths::data_file(REGISTERED_FILE);
+    let exe_text = exe.to_string_lossy().into_owned();
+    if fs::read_to_string(&marker).is_ok_and(|registered| registered == exe_text) {
+        return;
+    }
+    match register_for(&exe) {
+        Ok(()) => {
+            log::info!("Registered {}:// links", SCHEME);
+            let _ = fs::write(&marker, exe_text);
+        }
+        Err(err) => log::warn!("Could not register {}:// links: {}", SCHEME, err),
+    }
+}
+
+#[cfg(windows)]
+fn register_for(exe: &Path) -> Result<(), String> {
+    use std::process::Command;
+
+    let key = format!(r"HKCU\Software\Classes\{}", SCHEME);
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let entries = [
+        vec![key.clone(), "/ve".into(), "/d".into(), "URL:Interval Timer".into()],
+        vec![key.clone(), "/v".into(), "URL Protocol".into(), "/d".into(), String::new()],
+        vec![format!(r"{}\shell\open\command", key), "/ve".into(), "/d".into(), command],
+    ];
+    for args in entries {
+        let status = Command::new("reg")
+            .arg("add")
+            .args(&args)
+            .arg("/f")
+            .status()
+            .map_err(|err| err.to_string())?;
+        if !status.success() {
+            return Err(format!("reg add failed with {}", status));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn register_for(exe: &Path) -> Result<(), String> {
+    use std::process::Command;
+
+    let dir = dirs::data_dir().ok_or("No data directory")?.join("applications");
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let desktop = format!(
+        "[Desktop Entry]\nType=Application\nName=Interval Timer\nExec=\"{}\" %u\nNoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+        exe.display(),
+        SCHEME
+    );
+    let file_name = format!("{}.desktop", SCHEME);
+    fs::write(dir.join(&file_name), desktop).map_err(|err| err.to_string())?;
+    let status = Command::new("xdg-mime")
+        .args(["default", &file_name, &format!("x-scheme-handler/{}", SCHEME)])
+        .status()
+        .map_err(|err| err.to_string())?;
+    if status.success() { Ok(()) } else { Err(format!("xdg-mime failed with {}", status)) }
+}
+
+// Schemes come from the app bundle's Info.plist (CFBundleURLTypes), nothing to do at runtime
+#[cfg(target_os = "macos")]
+fn register_for(_exe: &Path) -> Result<(), String> {
+    Ok(())
+}